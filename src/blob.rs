@@ -0,0 +1,253 @@
+use reqwest::{Body, Method};
+use reqwest::header::Headers;
+use serde::Serialize;
+use serde_json;
+
+use std::io::{self, Cursor, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rest::{RestError, RestRequest, RestResult, encode_segment, expect_success, parse_response};
+use sobject::{SaveResult, record_path, sobject_path};
+
+/// Streams the contents of a blob field, such as `ContentVersion.VersionData` or
+/// `Attachment.Body`, into `writer` without holding the file in memory
+pub fn download<W: Write>(
+    request: &RestRequest,
+    sobject: &str,
+    id: &str,
+    field: &str,
+    writer: &mut W,
+) -> RestResult<u64> {
+    let path = record_path(sobject, id) + "/" + encode_segment(field).as_str();
+    let mut response = request
+        .send(
+            Method::Get,
+            request.url(path.as_str()).as_str(),
+            Headers::new(),
+            |builder| builder,
+        )
+        .and_then(expect_success)?;
+
+    io::copy(&mut response, writer).map_err(RestError::Io)
+}
+
+/// The largest blob a multipart request may carry for a `ContentVersion`
+pub const CONTENT_VERSION_LIMIT: u64 = 2 * 1024 * 1024 * 1024;
+/// The largest blob a multipart request may carry for other sObjects, such as an `Attachment`
+pub const BLOB_LIMIT: u64 = 500 * 1024 * 1024;
+
+fn upload_limit(sobject: &str) -> u64 {
+    match sobject {
+        "ContentVersion" => CONTENT_VERSION_LIMIT,
+        _ => BLOB_LIMIT,
+    }
+}
+
+/// The name of the multipart part carrying a record's fields
+fn entity_part(sobject: &str) -> String {
+    match sobject {
+        "ContentVersion" => "entity_content".to_owned(),
+        sobject => "entity_".to_owned() + sobject.to_lowercase().as_str(),
+    }
+}
+
+fn boundary() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("boundary_{:x}{:x}", now.as_secs(), now.subsec_nanos())
+}
+
+/// The multipart content placed before and after the blob itself
+fn multipart_frame(
+    boundary: &str,
+    sobject: &str,
+    metadata: &[u8],
+    blob_field: &str,
+    file_name: &str,
+) -> (Vec<u8>, Vec<u8>) {
+    let mut head = vec![];
+    head.extend(format!("--{}\r\n", boundary).into_bytes());
+    head.extend(
+        format!(
+            "Content-Disposition: form-data; name=\"{}\"\r\n",
+            entity_part(sobject)
+        ).into_bytes(),
+    );
+    head.extend(b"Content-Type: application/json\r\n\r\n".iter());
+    head.extend(metadata);
+    head.extend(format!("\r\n--{}\r\n", boundary).into_bytes());
+    head.extend(
+        format!(
+            "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
+            blob_field,
+            file_name.replace('"', "")
+        ).into_bytes(),
+    );
+    head.extend(b"Content-Type: application/octet-stream\r\n\r\n".iter());
+
+    let tail = format!("\r\n--{}--\r\n", boundary).into_bytes();
+
+    (head, tail)
+}
+
+/// Creates a record with a blob, such as a `ContentVersion` with its `VersionData`, streaming the
+/// blob from `blob` as the file part of a multipart request. `metadata` holds the other fields.
+/// When the blob's `length` is known it is checked against the upload limit of the sObject and
+/// the request is sent with a `Content-Length`, otherwise it is sent chunked.
+pub fn upload<T, R>(
+    request: &RestRequest,
+    sobject: &str,
+    blob_field: &str,
+    metadata: &T,
+    file_name: &str,
+    blob: R,
+    length: Option<u64>,
+) -> RestResult<SaveResult>
+where
+    T: Serialize,
+    R: Read + Send + 'static,
+{
+    if let Some(length) = length {
+        if length > upload_limit(sobject) {
+            return Err(RestError::InvalidRequest(format!(
+                "A {} blob can be at most {} bytes, {} were given",
+                sobject,
+                upload_limit(sobject),
+                length
+            )));
+        }
+    }
+
+    let metadata = serde_json::to_vec(metadata).map_err(|err| {
+        RestError::InvalidRequest(err.to_string())
+    })?;
+    let boundary = boundary();
+    let (head, tail) = multipart_frame(
+        boundary.as_str(),
+        sobject,
+        metadata.as_slice(),
+        blob_field,
+        file_name,
+    );
+
+    let mut headers = Headers::new();
+    headers.set_raw(
+        "Content-Type",
+        "multipart/form-data; boundary=".to_owned() + boundary.as_str(),
+    );
+
+    let framing = (head.len() + tail.len()) as u64;
+    let content = Cursor::new(head).chain(blob).chain(Cursor::new(tail));
+    let body = match length {
+        Some(length) => Body::sized(content, framing + length),
+        None => Body::new(content),
+    };
+
+    request
+        .send(
+            Method::Post,
+            request.url(sobject_path(sobject).as_str()).as_str(),
+            headers,
+            |builder| builder.body(body),
+        )
+        .and_then(parse_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use std::io::Cursor;
+
+    use blob::{BLOB_LIMIT, download, multipart_frame, upload};
+    use http::HttpClient;
+    use rest::{RestError, RestRequest};
+
+    #[test]
+    fn test_frames_multipart_upload() {
+        let (head, tail) = multipart_frame(
+            "b1",
+            "ContentVersion",
+            br#"{"Title":"Report"}"#,
+            "VersionData",
+            "report.pdf",
+        );
+
+        assert_eq!(
+            "--b1\r\n\
+             Content-Disposition: form-data; name=\"entity_content\"\r\n\
+             Content-Type: application/json\r\n\r\n\
+             {\"Title\":\"Report\"}\r\n\
+             --b1\r\n\
+             Content-Disposition: form-data; name=\"VersionData\"; filename=\"report.pdf\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            String::from_utf8(head).unwrap()
+        );
+        assert_eq!("\r\n--b1--\r\n", String::from_utf8(tail).unwrap());
+    }
+
+    #[test]
+    fn test_streams_blob_download() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock("GET", "/services/data/vXY.Z/sobjects/ContentVersion/068D/VersionData");
+        m.with_status(200).with_body("%PDF-1.4 blob");
+        m.create();
+        let mut output = vec![];
+
+        let written = download(&request, "ContentVersion", "068D", "VersionData", &mut output);
+
+        m.remove();
+
+        assert_eq!(13, written.unwrap());
+        assert_eq!(b"%PDF-1.4 blob".to_vec(), output);
+    }
+
+    #[test]
+    fn test_rejects_blobs_over_the_upload_limit() {
+        let client = HttpClient::new().unwrap();
+        let request = RestRequest::new("http://127.0.0.1/", "vXY.Z", "test-token", &client);
+
+        let result = upload(
+            &request,
+            "Attachment",
+            "Body",
+            &json!({"Name": "large.bin", "ParentId": "001D"}),
+            "large.bin",
+            Cursor::new(vec![]),
+            Some(BLOB_LIMIT + 1),
+        );
+
+        match result {
+            Err(RestError::InvalidRequest(_)) => (),
+            _ => panic!("Failed to reject a blob over the upload limit"),
+        };
+    }
+
+    #[test]
+    fn test_uploads_multipart_record() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock("POST", "/services/data/vXY.Z/sobjects/Attachment");
+        m.with_status(201).with_body(
+            r#"{"id": "00PD000000A1b2c", "success": true, "errors": []}"#,
+        );
+        m.create();
+
+        let result = upload(
+            &request,
+            "Attachment",
+            "Body",
+            &json!({"Name": "notes.txt", "ParentId": "001D"}),
+            "notes.txt",
+            Cursor::new(b"some notes".to_vec()),
+            Some(10),
+        );
+
+        m.remove();
+
+        assert_eq!(Some("00PD000000A1b2c".to_owned()), result.unwrap().id);
+    }
+}
@@ -0,0 +1,675 @@
+use reqwest::{Body, Method, RequestBuilder, Response};
+use reqwest::header::Headers;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Map, Value};
+
+use std::io::{self, Read, Write};
+
+use bulk2::Operation;
+use rest::{ApiFailure, RestError, RestFailure, RestRequest, RestResult};
+
+pub static ASYNC_BASE: &'static str = "services/async/";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ContentType {
+    #[serde(rename = "CSV")]
+    Csv,
+    #[serde(rename = "XML")]
+    Xml,
+    #[serde(rename = "JSON")]
+    Json,
+}
+
+impl ContentType {
+    fn mime(&self) -> &'static str {
+        match *self {
+            ContentType::Csv => "text/csv",
+            ContentType::Xml => "application/xml",
+            ContentType::Json => "application/json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ConcurrencyMode {
+    Parallel,
+    Serial,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum BulkJobState {
+    Open,
+    Closed,
+    Aborted,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum BulkBatchState {
+    Queued,
+    InProgress,
+    Completed,
+    Failed,
+    /// The original batch of a PK chunked query, its chunks are processed as separate batches
+    #[serde(rename = "Not Processed")]
+    NotProcessed,
+}
+
+impl BulkBatchState {
+    pub fn is_finished(&self) -> bool {
+        match *self {
+            BulkBatchState::Completed | BulkBatchState::Failed | BulkBatchState::NotProcessed => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSpec {
+    pub operation: Operation,
+    pub object: String,
+    pub content_type: ContentType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency_mode: Option<ConcurrencyMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id_field_name: Option<String>,
+}
+
+impl JobSpec {
+    pub fn new<S: Into<String>>(operation: Operation, object: S, content_type: ContentType) -> JobSpec {
+        JobSpec {
+            operation: operation,
+            object: object.into(),
+            content_type: content_type,
+            concurrency_mode: None,
+            external_id_field_name: None,
+        }
+    }
+
+    /// Processes the job's batches one at a time, which avoids lock contention on related records
+    pub fn serial(mut self) -> JobSpec {
+        self.concurrency_mode = Some(ConcurrencyMode::Serial);
+        self
+    }
+}
+
+/// Options for the `Sforce-Enable-PKChunking` header, which splits a query job into batches by
+/// ranges of record Ids
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PkChunking {
+    pub chunk_size: Option<u32>,
+    pub parent: Option<String>,
+    pub start_row: Option<String>,
+}
+
+impl PkChunking {
+    pub fn header_value(&self) -> String {
+        let mut options = vec![];
+
+        if let Some(chunk_size) = self.chunk_size {
+            options.push(format!("chunkSize={}", chunk_size));
+        }
+        if let Some(ref parent) = self.parent {
+            options.push(format!("parent={}", parent));
+        }
+        if let Some(ref start_row) = self.start_row {
+            options.push(format!("startRow={}", start_row));
+        }
+
+        if options.is_empty() {
+            "true".to_owned()
+        } else {
+            options.join("; ")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkJobInfo {
+    pub id: String,
+    pub operation: Operation,
+    pub object: String,
+    pub state: BulkJobState,
+    #[serde(default)]
+    pub content_type: Option<ContentType>,
+    #[serde(default)]
+    pub concurrency_mode: Option<ConcurrencyMode>,
+    #[serde(default)]
+    pub number_batches_queued: u64,
+    #[serde(default)]
+    pub number_batches_in_progress: u64,
+    #[serde(default)]
+    pub number_batches_completed: u64,
+    #[serde(default)]
+    pub number_batches_failed: u64,
+    #[serde(default)]
+    pub number_batches_total: u64,
+    #[serde(default)]
+    pub number_records_processed: u64,
+    #[serde(default)]
+    pub number_records_failed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkBatchInfo {
+    pub id: String,
+    pub job_id: String,
+    pub state: BulkBatchState,
+    #[serde(default)]
+    pub state_message: Option<String>,
+    #[serde(default)]
+    pub number_records_processed: u64,
+    #[serde(default)]
+    pub number_records_failed: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchInfoList {
+    #[serde(rename = "batchInfo")]
+    batch_info: Vec<BulkBatchInfo>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct BulkFailure {
+    exception_code: String,
+    exception_message: String,
+}
+
+/// Unescapes the entities the Bulk API uses in XML text content
+fn xml_text(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The contents of each `tag` element in `content`, in document order
+fn xml_elements<'a>(content: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = "<".to_owned() + tag;
+    let close = "</".to_owned() + tag + ">";
+    let mut elements = vec![];
+    let mut rest = content;
+
+    while let Some(start) = rest.find(open.as_str()) {
+        let after = &rest[start + open.len()..];
+
+        match after.chars().next() {
+            Some('>') | Some(' ') => (),
+            _ => {
+                rest = after;
+                continue;
+            }
+        }
+
+        let body_start = match after.find('>') {
+            Some(index) => index + 1,
+            None => break,
+        };
+
+        if after[..body_start].ends_with("/>") {
+            elements.push("");
+            rest = &after[body_start..];
+            continue;
+        }
+
+        match after[body_start..].find(close.as_str()) {
+            Some(end) => {
+                elements.push(&after[body_start..body_start + end]);
+                rest = &after[body_start + end + close.len()..];
+            }
+            None => break,
+        }
+    }
+
+    elements
+}
+
+/// Converts an element with only simple children into a json object. Counts and timings become
+/// numbers so that the Bulk API's XML and json responses deserialize into the same types.
+fn xml_object(content: &str) -> Value {
+    let mut fields = Map::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find('<') {
+        let after = &rest[start + 1..];
+        let name_end = match after.find(|c: char| c == '>' || c == ' ' || c == '/') {
+            Some(index) => index,
+            None => break,
+        };
+        let name = &after[..name_end];
+        let close = "</".to_owned() + name + ">";
+
+        let body_start = match after.find('>') {
+            Some(index) => index + 1,
+            None => break,
+        };
+        let (text, next) = match after[body_start..].find(close.as_str()) {
+            Some(end) => (
+                &after[body_start..body_start + end],
+                &after[body_start + end + close.len()..],
+            ),
+            None => ("", &after[body_start..]),
+        };
+
+        let text = xml_text(text.trim());
+        let value = if name.starts_with("number") || name.ends_with("Time") {
+            text.parse::<u64>().map(Value::from).unwrap_or(Value::String(text))
+        } else {
+            Value::String(text)
+        };
+
+        fields.insert(name.to_owned(), value);
+        rest = next;
+    }
+
+    Value::Object(fields)
+}
+
+/// Reads a Bulk API response body, which is json or XML depending on the job's content type
+fn parse_info<T: DeserializeOwned>(content: &str, tag: &str) -> RestResult<T> {
+    let value = if content.trim_left().starts_with('<') {
+        match xml_elements(content, tag).first() {
+            Some(element) => xml_object(element),
+            None => return Err(RestError::ResponseParseFailure),
+        }
+    } else {
+        serde_json::from_str(content).map_err(|_| RestError::ResponseParseFailure)?
+    };
+
+    serde_json::from_value(value).map_err(|_| RestError::ResponseParseFailure)
+}
+
+/// Turns a failed Bulk API response into an error. The Bulk API answers a rejected session with
+/// `400 InvalidSessionId`, which is reported as a 401 so that callers re-authenticate.
+fn bulk_failure(response: Response) -> RestError {
+    let status = response.status().to_u16();
+    let content = match read_content(response) {
+        Ok(content) => content,
+        Err(err) => return err,
+    };
+
+    let parsed = if content.trim_left().starts_with('<') {
+        xml_elements(content.as_str(), "error")
+            .first()
+            .and_then(|element| serde_json::from_value::<BulkFailure>(xml_object(element)).ok())
+    } else {
+        serde_json::from_str::<BulkFailure>(content.as_str()).ok()
+    };
+    let failure = parsed.unwrap_or_default();
+
+    RestError::API(RestFailure {
+        status: if failure.exception_code == "InvalidSessionId" { 401 } else { status },
+        errors: vec![
+            ApiFailure {
+                message: failure.exception_message,
+                error_code: failure.exception_code,
+                ..ApiFailure::default()
+            },
+        ],
+    })
+}
+
+fn read_content(mut response: Response) -> RestResult<String> {
+    let mut content = String::new();
+    response.read_to_string(&mut content).map_err(RestError::Io)?;
+    Ok(content)
+}
+
+fn async_url(request: &RestRequest, path: &str) -> String {
+    request.instance_url(
+        (ASYNC_BASE.to_owned() + request.version().trim_left_matches('v') + path).as_str(),
+    )
+}
+
+fn session_headers(request: &RestRequest, content_type: &str) -> Headers {
+    let mut headers = Headers::new();
+    headers.set_raw("X-SFDC-Session", request.token().to_owned());
+    headers.set_raw("Content-Type", content_type.to_owned());
+    headers
+}
+
+fn send<F>(request: &RestRequest, method: Method, path: &str, headers: Headers, body: F) -> RestResult<Response>
+where
+    F: FnOnce(RequestBuilder) -> RequestBuilder,
+{
+    let response = request.send(method, async_url(request, path).as_str(), headers, body)?;
+
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(bulk_failure(response))
+    }
+}
+
+fn send_info<T: DeserializeOwned>(
+    request: &RestRequest,
+    method: Method,
+    path: &str,
+    body: Option<&Value>,
+    headers: Headers,
+    tag: &str,
+) -> RestResult<T> {
+    let response = send(request, method, path, headers, |builder| match body {
+        Some(body) => builder.json(body),
+        None => builder,
+    })?;
+
+    parse_info(read_content(response)?.as_str(), tag)
+}
+
+fn job_path(job_id: &str) -> String {
+    "/job/".to_owned() + job_id
+}
+
+fn batch_path(job_id: &str, batch_id: &str) -> String {
+    job_path(job_id) + "/batch/" + batch_id
+}
+
+/// Creates a job, splitting query jobs into batches by Id range when `pk_chunking` is given
+pub fn create_job(
+    request: &RestRequest,
+    spec: &JobSpec,
+    pk_chunking: Option<&PkChunking>,
+) -> RestResult<BulkJobInfo> {
+    let mut headers = session_headers(request, "application/json");
+
+    if let Some(pk_chunking) = pk_chunking {
+        headers.set_raw("Sforce-Enable-PKChunking", pk_chunking.header_value());
+    }
+
+    let body = serde_json::to_value(spec).map_err(|err| {
+        RestError::InvalidRequest(err.to_string())
+    })?;
+
+    send_info(request, Method::Post, "/job", Some(&body), headers, "jobInfo")
+}
+
+pub fn job(request: &RestRequest, job_id: &str) -> RestResult<BulkJobInfo> {
+    let headers = session_headers(request, "application/json");
+    send_info(request, Method::Get, job_path(job_id).as_str(), None, headers, "jobInfo")
+}
+
+fn set_job_state(request: &RestRequest, job_id: &str, state: BulkJobState) -> RestResult<BulkJobInfo> {
+    let headers = session_headers(request, "application/json");
+    let body = json!({ "state": state });
+
+    send_info(request, Method::Post, job_path(job_id).as_str(), Some(&body), headers, "jobInfo")
+}
+
+/// Closes a job to further batches, batches already added are still processed
+pub fn close_job(request: &RestRequest, job_id: &str) -> RestResult<BulkJobInfo> {
+    set_job_state(request, job_id, BulkJobState::Closed)
+}
+
+pub fn abort_job(request: &RestRequest, job_id: &str) -> RestResult<BulkJobInfo> {
+    set_job_state(request, job_id, BulkJobState::Aborted)
+}
+
+/// Adds a batch of records, or the SOQL text of a query job, in the job's content type
+pub fn add_batch<B: Into<Body>>(
+    request: &RestRequest,
+    job_id: &str,
+    content_type: ContentType,
+    data: B,
+) -> RestResult<BulkBatchInfo> {
+    let path = job_path(job_id) + "/batch";
+    let headers = session_headers(request, content_type.mime());
+    let response = send(request, Method::Post, path.as_str(), headers, |builder| builder.body(data))?;
+
+    parse_info(read_content(response)?.as_str(), "batchInfo")
+}
+
+pub fn batch(request: &RestRequest, job_id: &str, batch_id: &str) -> RestResult<BulkBatchInfo> {
+    let headers = session_headers(request, "application/json");
+    send_info(
+        request,
+        Method::Get,
+        batch_path(job_id, batch_id).as_str(),
+        None,
+        headers,
+        "batchInfo",
+    )
+}
+
+/// Lists every batch of a job, including the batches PK chunking created
+pub fn batches(request: &RestRequest, job_id: &str) -> RestResult<Vec<BulkBatchInfo>> {
+    let path = job_path(job_id) + "/batch";
+    let headers = session_headers(request, "application/json");
+    let response = send(request, Method::Get, path.as_str(), headers, |builder| builder)?;
+    let content = read_content(response)?;
+
+    if content.trim_left().starts_with('<') {
+        xml_elements(content.as_str(), "batchInfo")
+            .into_iter()
+            .map(|element| {
+                serde_json::from_value(xml_object(element)).map_err(|_| RestError::ResponseParseFailure)
+            })
+            .collect()
+    } else {
+        serde_json::from_str::<BatchInfoList>(content.as_str())
+            .map(|list| list.batch_info)
+            .map_err(|_| RestError::ResponseParseFailure)
+    }
+}
+
+/// Streams the per record results of an ingest batch into `writer`
+pub fn batch_results<W: Write>(
+    request: &RestRequest,
+    job_id: &str,
+    batch_id: &str,
+    writer: &mut W,
+) -> RestResult<u64> {
+    let path = batch_path(job_id, batch_id) + "/result";
+    let headers = session_headers(request, "application/json");
+    let mut response = send(request, Method::Get, path.as_str(), headers, |builder| builder)?;
+
+    io::copy(&mut response, writer).map_err(RestError::Io)
+}
+
+/// Lists the result sets of a completed query batch
+pub fn query_result_ids(request: &RestRequest, job_id: &str, batch_id: &str) -> RestResult<Vec<String>> {
+    let path = batch_path(job_id, batch_id) + "/result";
+    let headers = session_headers(request, "application/json");
+    let response = send(request, Method::Get, path.as_str(), headers, |builder| builder)?;
+    let content = read_content(response)?;
+
+    if content.trim_left().starts_with('<') {
+        Ok(
+            xml_elements(content.as_str(), "result")
+                .into_iter()
+                .map(|id| xml_text(id.trim()))
+                .collect(),
+        )
+    } else {
+        serde_json::from_str(content.as_str()).map_err(|_| RestError::ResponseParseFailure)
+    }
+}
+
+/// Streams one result set of a completed query batch into `writer`
+pub fn query_result<W: Write>(
+    request: &RestRequest,
+    job_id: &str,
+    batch_id: &str,
+    result_id: &str,
+    writer: &mut W,
+) -> RestResult<u64> {
+    let path = batch_path(job_id, batch_id) + "/result/" + result_id;
+    let headers = session_headers(request, "application/json");
+    let mut response = send(request, Method::Get, path.as_str(), headers, |builder| builder)?;
+
+    io::copy(&mut response, writer).map_err(RestError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use bulk1::{BulkBatchState, BulkJobState, ContentType, JobSpec, PkChunking, add_batch,
+                batch_results, batches, create_job, job, query_result, query_result_ids};
+    use bulk2::Operation;
+    use http::HttpClient;
+    use rest::{RestError, RestRequest};
+
+    #[test]
+    fn test_formats_pk_chunking_header() {
+        assert_eq!("true", PkChunking::default().header_value());
+        assert_eq!(
+            "chunkSize=50000; parent=Account",
+            PkChunking {
+                chunk_size: Some(50000),
+                parent: Some("Account".to_owned()),
+                start_row: None,
+            }.header_value()
+        );
+    }
+
+    #[test]
+    fn test_creates_pk_chunked_job() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock("POST", "/services/async/XY.Z/job");
+        m.with_status(201)
+            .with_body(
+                json!({
+                    "id": "750B",
+                    "operation": "query",
+                    "object": "Account",
+                    "state": "Open",
+                    "concurrencyMode": "Serial",
+                    "contentType": "CSV"
+                }).to_string()
+                    .as_str(),
+            )
+            .match_header("X-SFDC-Session", "test-token")
+            .match_header("Sforce-Enable-PKChunking", "chunkSize=1000");
+        m.create();
+
+        let spec = JobSpec::new(Operation::Query, "Account", ContentType::Csv).serial();
+        let chunking = PkChunking {
+            chunk_size: Some(1000),
+            ..PkChunking::default()
+        };
+        let created = create_job(&request, &spec, Some(&chunking));
+
+        m.remove();
+
+        let created = created.unwrap();
+        assert_eq!("750B", created.id);
+        assert_eq!(BulkJobState::Open, created.state);
+    }
+
+    #[test]
+    fn test_reads_xml_batch_info() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut add = mock("POST", "/services/async/XY.Z/job/750X/batch");
+        add.with_status(201)
+            .with_body(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<batchInfo xmlns="http://www.force.com/2009/06/asyncapi/dataload">
+ <id>751X</id>
+ <jobId>750X</jobId>
+ <state>Queued</state>
+ <numberRecordsProcessed>0</numberRecordsProcessed>
+</batchInfo>"#,
+            )
+            .match_header("content-type", "text/csv");
+        add.create();
+        let mut list = mock("GET", "/services/async/XY.Z/job/750X/batch");
+        list.with_status(200).with_body(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<batchInfoList xmlns="http://www.force.com/2009/06/asyncapi/dataload">
+ <batchInfo><id>751X</id><jobId>750X</jobId><state>Not Processed</state></batchInfo>
+ <batchInfo><id>751Y</id><jobId>750X</jobId><state>Completed</state>
+  <numberRecordsProcessed>1000</numberRecordsProcessed></batchInfo>
+</batchInfoList>"#,
+        );
+        list.create();
+        let mut results = mock("GET", "/services/async/XY.Z/job/750X/batch/751Y/result");
+        results
+            .with_status(200)
+            .with_body(
+                r#"<result-list xmlns="http://www.force.com/2009/06/asyncapi/dataload"><result>752Y</result></result-list>"#,
+            )
+            .match_header("X-SFDC-Session", "test-token");
+        results.create();
+
+        let added = add_batch(&request, "750X", ContentType::Csv, "SELECT Id FROM Account".to_owned());
+        let listed = batches(&request, "750X");
+        let result_ids = query_result_ids(&request, "750X", "751Y");
+
+        add.remove();
+        list.remove();
+        results.remove();
+
+        assert_eq!(BulkBatchState::Queued, added.unwrap().state);
+        let listed = listed.unwrap();
+        assert_eq!(BulkBatchState::NotProcessed, listed[0].state);
+        assert_eq!(1000, listed[1].number_records_processed);
+        assert_eq!(vec!["752Y".to_owned()], result_ids.unwrap());
+    }
+
+    #[test]
+    fn test_streams_results_with_session_header() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut ingest = mock("GET", "/services/async/XY.Z/job/750R/batch/751R/result");
+        ingest
+            .with_status(200)
+            .with_body("\"Id\",\"Success\"\n\"001R\",\"true\"\n")
+            .match_header("X-SFDC-Session", "test-token");
+        ingest.create();
+        let mut query = mock("GET", "/services/async/XY.Z/job/750R/batch/751Q/result/752Q");
+        query
+            .with_status(200)
+            .with_body("\"Id\"\n\"001Q\"\n")
+            .match_header("X-SFDC-Session", "test-token");
+        query.create();
+        let mut ingest_output = vec![];
+        let mut query_output = vec![];
+
+        let ingested = batch_results(&request, "750R", "751R", &mut ingest_output);
+        let queried = query_result(&request, "750R", "751Q", "752Q", &mut query_output);
+
+        ingest.remove();
+        query.remove();
+
+        assert_eq!(b"\"Id\",\"Success\"\n\"001R\",\"true\"\n".to_vec(), ingest_output);
+        assert_eq!(ingest_output.len() as u64, ingested.unwrap());
+        assert_eq!(b"\"Id\"\n\"001Q\"\n".to_vec(), query_output);
+        assert_eq!(query_output.len() as u64, queried.unwrap());
+    }
+
+    #[test]
+    fn test_reports_invalid_session_as_unauthorized() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock("GET", "/services/async/XY.Z/job/750E");
+        m.with_status(400).with_body(
+            r#"{"exceptionCode": "InvalidSessionId", "exceptionMessage": "Invalid session id"}"#,
+        );
+        m.create();
+
+        let res = job(&request, "750E");
+
+        m.remove();
+
+        match res {
+            Err(RestError::API(ref failure)) => {
+                assert_eq!(401, failure.status);
+                assert_eq!("InvalidSessionId", failure.errors[0].error_code);
+            }
+            _ => panic!("Failed to report an invalid session"),
+        };
+    }
+}
@@ -0,0 +1,553 @@
+use csv;
+use reqwest::{Body, Method, Response};
+use reqwest::header::Headers;
+use serde::de::DeserializeOwned;
+
+use std::io::{self, BufRead, BufReader, Write};
+
+use rest::{RestError, RestRequest, RestResult, encode_query, expect_success, header};
+use {SFClient, SFClientResult};
+
+/// The most raw CSV data a single Bulk 2.0 ingest job accepts
+pub const UPLOAD_LIMIT: usize = 100 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum Operation {
+    #[serde(rename = "insert")]
+    Insert,
+    #[serde(rename = "update")]
+    Update,
+    #[serde(rename = "upsert")]
+    Upsert,
+    #[serde(rename = "delete")]
+    Delete,
+    #[serde(rename = "hardDelete")]
+    HardDelete,
+    #[serde(rename = "query")]
+    Query,
+    #[serde(rename = "queryAll")]
+    QueryAll,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum JobState {
+    Open,
+    UploadComplete,
+    InProgress,
+    Aborted,
+    JobComplete,
+    Failed,
+}
+
+impl JobState {
+    /// Whether the job has stopped processing, successfully or not
+    pub fn is_finished(&self) -> bool {
+        match *self {
+            JobState::JobComplete | JobState::Failed | JobState::Aborted => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestJobRequest {
+    pub object: String,
+    pub operation: Operation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id_field_name: Option<String>,
+    pub content_type: String,
+    pub line_ending: String,
+}
+
+impl IngestJobRequest {
+    pub fn new<S: Into<String>>(object: S, operation: Operation) -> IngestJobRequest {
+        IngestJobRequest {
+            object: object.into(),
+            operation: operation,
+            external_id_field_name: None,
+            content_type: "CSV".to_owned(),
+            line_ending: "LF".to_owned(),
+        }
+    }
+
+    pub fn upsert<S: Into<String>>(object: S, external_id_field_name: S) -> IngestJobRequest {
+        let mut request = IngestJobRequest::new(object, Operation::Upsert);
+        request.external_id_field_name = Some(external_id_field_name.into());
+        request
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobInfo {
+    pub id: String,
+    pub operation: Operation,
+    pub object: Option<String>,
+    pub state: JobState,
+    #[serde(default)]
+    pub created_date: Option<String>,
+    #[serde(default)]
+    pub system_modstamp: Option<String>,
+    #[serde(default)]
+    pub number_records_processed: Option<u64>,
+    #[serde(default)]
+    pub number_records_failed: Option<u64>,
+    #[serde(default)]
+    pub error_message: Option<String>,
+    #[serde(default)]
+    pub content_url: Option<String>,
+}
+
+/// The result sets an ingest job can be asked for once it has finished
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IngestResults {
+    Successful,
+    Failed,
+    Unprocessed,
+}
+
+impl IngestResults {
+    fn path(&self) -> &'static str {
+        match *self {
+            IngestResults::Successful => "/successfulResults/",
+            IngestResults::Failed => "/failedResults/",
+            IngestResults::Unprocessed => "/unprocessedrecords/",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryJobRequest {
+    pub operation: Operation,
+    pub query: String,
+    pub content_type: String,
+    pub line_ending: String,
+}
+
+impl QueryJobRequest {
+    pub fn new<S: Into<String>>(query: S) -> QueryJobRequest {
+        QueryJobRequest {
+            operation: Operation::Query,
+            query: query.into(),
+            content_type: "CSV".to_owned(),
+            line_ending: "LF".to_owned(),
+        }
+    }
+
+    /// A query that also returns deleted and archived records
+    pub fn query_all<S: Into<String>>(query: S) -> QueryJobRequest {
+        QueryJobRequest {
+            operation: Operation::QueryAll,
+            ..QueryJobRequest::new(query)
+        }
+    }
+}
+
+/// Describes one page of query results, `locator` names the next page when there is one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultsPage {
+    pub locator: Option<String>,
+    pub records: Option<u64>,
+}
+
+/// Splits CSV data into chunks no larger than a size limit, breaking only between records and
+/// repeating the header row at the start of every chunk
+pub struct CsvChunker<R: BufRead> {
+    reader: R,
+    header: Vec<u8>,
+    pending: Option<Vec<u8>>,
+    limit: usize,
+}
+
+impl<R: BufRead> CsvChunker<R> {
+    pub fn new(mut reader: R, limit: usize) -> io::Result<CsvChunker<R>> {
+        let header = read_record(&mut reader)?.unwrap_or_default();
+
+        Ok(CsvChunker {
+            reader: reader,
+            header: header,
+            pending: None,
+            limit: limit,
+        })
+    }
+
+    pub fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut chunk = self.header.clone();
+        let mut records = 0;
+
+        loop {
+            let record = match self.pending.take() {
+                Some(record) => Some(record),
+                None => read_record(&mut self.reader)?,
+            };
+
+            match record {
+                Some(record) => {
+                    if records > 0 && chunk.len() + record.len() > self.limit {
+                        self.pending = Some(record);
+                        break;
+                    }
+
+                    chunk.extend(record);
+                    records += 1;
+                }
+                None => break,
+            }
+        }
+
+        if records > 0 { Ok(Some(chunk)) } else { Ok(None) }
+    }
+}
+
+/// Reads a single CSV record, which may span several lines when a quoted value holds a newline
+fn read_record<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut record = vec![];
+
+    loop {
+        let read = reader.read_until(b'\n', &mut record)?;
+        let quotes = record.iter().filter(|byte| **byte == b'"').count();
+
+        if read == 0 || quotes % 2 == 0 {
+            break;
+        }
+    }
+
+    if record.is_empty() {
+        Ok(None)
+    } else {
+        if !record.ends_with(b"\n") {
+            record.push(b'\n');
+        }
+
+        Ok(Some(record))
+    }
+}
+
+fn job_path(job_id: &str) -> String {
+    "/jobs/ingest/".to_owned() + job_id
+}
+
+fn csv_headers() -> Headers {
+    let mut headers = Headers::new();
+    headers.set_raw("Content-Type", "text/csv");
+    headers
+}
+
+pub fn create_ingest_job(request: &RestRequest, job: &IngestJobRequest) -> RestResult<JobInfo> {
+    request.send_json(Method::Post, request.url("/jobs/ingest").as_str(), job)
+}
+
+/// Uploads the CSV data for a job, a job only accepts a single upload
+pub fn upload_job_data<B: Into<Body>>(request: &RestRequest, job_id: &str, data: B) -> RestResult<()> {
+    request
+        .send(
+            Method::Put,
+            request.url((job_path(job_id) + "/batches").as_str()).as_str(),
+            csv_headers(),
+            |builder| builder.body(data),
+        )
+        .and_then(expect_success)
+        .map(|_| ())
+}
+
+fn set_job_state(request: &RestRequest, job_id: &str, state: JobState) -> RestResult<JobInfo> {
+    request.send_json(
+        Method::Patch,
+        request.url(job_path(job_id).as_str()).as_str(),
+        &json!({ "state": state }),
+    )
+}
+
+/// Marks the upload as complete so that the job is queued for processing
+pub fn close_ingest_job(request: &RestRequest, job_id: &str) -> RestResult<JobInfo> {
+    set_job_state(request, job_id, JobState::UploadComplete)
+}
+
+pub fn abort_ingest_job(request: &RestRequest, job_id: &str) -> RestResult<JobInfo> {
+    set_job_state(request, job_id, JobState::Aborted)
+}
+
+pub fn ingest_job(request: &RestRequest, job_id: &str) -> RestResult<JobInfo> {
+    request.get(request.url(job_path(job_id).as_str()).as_str())
+}
+
+pub fn delete_ingest_job(request: &RestRequest, job_id: &str) -> RestResult<()> {
+    request.delete(request.url(job_path(job_id).as_str()).as_str())
+}
+
+/// Streams one of a finished job's CSV result sets into `writer`
+pub fn ingest_results<W: Write>(
+    request: &RestRequest,
+    job_id: &str,
+    results: IngestResults,
+    writer: &mut W,
+) -> RestResult<u64> {
+    let path = job_path(job_id) + results.path();
+    let mut response = request
+        .send(
+            Method::Get,
+            request.url(path.as_str()).as_str(),
+            Headers::new(),
+            |builder| builder,
+        )
+        .and_then(expect_success)?;
+
+    io::copy(&mut response, writer).map_err(RestError::Io)
+}
+
+fn query_job_path(job_id: &str) -> String {
+    "/jobs/query/".to_owned() + job_id
+}
+
+pub fn create_query_job(request: &RestRequest, job: &QueryJobRequest) -> RestResult<JobInfo> {
+    request.send_json(Method::Post, request.url("/jobs/query").as_str(), job)
+}
+
+pub fn query_job(request: &RestRequest, job_id: &str) -> RestResult<JobInfo> {
+    request.get(request.url(query_job_path(job_id).as_str()).as_str())
+}
+
+pub fn abort_query_job(request: &RestRequest, job_id: &str) -> RestResult<JobInfo> {
+    request.send_json(
+        Method::Patch,
+        request.url(query_job_path(job_id).as_str()).as_str(),
+        &json!({ "state": JobState::Aborted }),
+    )
+}
+
+pub fn delete_query_job(request: &RestRequest, job_id: &str) -> RestResult<()> {
+    request.delete(request.url(query_job_path(job_id).as_str()).as_str())
+}
+
+/// Opens a page of a completed query job's CSV results, leaving its rows to be read from the
+/// returned response
+pub fn open_query_results(
+    request: &RestRequest,
+    job_id: &str,
+    locator: Option<&str>,
+) -> RestResult<(ResultsPage, Response)> {
+    let mut path = query_job_path(job_id) + "/results";
+
+    if let Some(locator) = locator {
+        path = path + "?locator=" + encode_query(locator).as_str();
+    }
+
+    let response = request
+        .send(
+            Method::Get,
+            request.url(path.as_str()).as_str(),
+            Headers::new(),
+            |builder| builder,
+        )
+        .and_then(expect_success)?;
+
+    let page = ResultsPage {
+        locator: header(&response, "Sforce-Locator").and_then(|locator| {
+            if locator.is_empty() || locator == "null" {
+                None
+            } else {
+                Some(locator)
+            }
+        }),
+        records: header(&response, "Sforce-NumberOfRecords").and_then(|count| count.parse().ok()),
+    };
+
+    Ok((page, response))
+}
+
+/// Streams a page of a completed query job's CSV results into `writer`. Every page starts with
+/// the header row, pass `skip_header` to leave it out when appending pages to one output.
+pub fn query_results<W: Write>(
+    request: &RestRequest,
+    job_id: &str,
+    locator: Option<&str>,
+    skip_header: bool,
+    writer: &mut W,
+) -> RestResult<ResultsPage> {
+    let (page, response) = open_query_results(request, job_id, locator)?;
+    let mut reader = BufReader::new(response);
+
+    if skip_header {
+        read_record(&mut reader).map_err(RestError::Io)?;
+    }
+
+    io::copy(&mut reader, writer).map_err(RestError::Io)?;
+
+    Ok(page)
+}
+
+/// Reads the CSV rows of a completed query job straight from the response, fetching each page of
+/// results once the rows before it are used up
+pub struct QueryRows<'c, T> {
+    client: &'c mut SFClient,
+    job_id: String,
+    locator: Option<String>,
+    rows: Option<csv::DeserializeRecordsIntoIter<Response, T>>,
+    done: bool,
+}
+
+impl<'c, T: DeserializeOwned> QueryRows<'c, T> {
+    pub fn new(client: &'c mut SFClient, job_id: &str) -> QueryRows<'c, T> {
+        QueryRows {
+            client: client,
+            job_id: job_id.to_owned(),
+            locator: None,
+            rows: None,
+            done: false,
+        }
+    }
+
+    fn fetch(&mut self) -> SFClientResult<csv::DeserializeRecordsIntoIter<Response, T>> {
+        let (page, response) = {
+            let job_id = self.job_id.as_str();
+            let locator = self.locator.as_ref().map(|locator| locator.as_str());
+
+            self.client.rest_at_most_once(
+                |request| open_query_results(request, job_id, locator),
+            )?
+        };
+
+        self.done = page.locator.is_none();
+        self.locator = page.locator;
+
+        Ok(csv::Reader::from_reader(response).into_deserialize())
+    }
+}
+
+impl<'c, T: DeserializeOwned> Iterator for QueryRows<'c, T> {
+    type Item = SFClientResult<T>;
+
+    fn next(&mut self) -> Option<SFClientResult<T>> {
+        loop {
+            let row = match self.rows {
+                Some(ref mut rows) => rows.next(),
+                None => None,
+            };
+
+            match row {
+                Some(Ok(row)) => return Some(Ok(row)),
+                Some(Err(_)) => {
+                    self.rows = None;
+                    self.done = true;
+                    return Some(Err(RestError::ResponseParseFailure.into()));
+                }
+                None => self.rows = None,
+            };
+
+            if self.done {
+                return None;
+            }
+
+            match self.fetch() {
+                Ok(rows) => self.rows = Some(rows),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::{mock, Mock};
+
+    use std::io::Cursor;
+
+    use bulk2::{CsvChunker, IngestJobRequest, IngestResults, JobState, Operation, close_ingest_job,
+                create_ingest_job, ingest_results, upload_job_data};
+    use http::HttpClient;
+    use rest::RestRequest;
+
+    fn job_info(id: &str, state: &str) -> String {
+        json!({
+            "id": id,
+            "operation": "insert",
+            "object": "Account",
+            "state": state,
+            "contentType": "CSV"
+        }).to_string()
+    }
+
+    fn bulk_mock(method: &str, path: &str, code: usize, body: &str) -> Mock {
+        let mut m = mock(method, ("/services/data/vXY.Z".to_owned() + path).as_str());
+        m.with_status(code).with_body(body);
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_chunks_csv_between_records() {
+        let data = "Name,Description\na,one\nb,\"two\nlines\"\nc,three\n";
+        let mut chunker = CsvChunker::new(Cursor::new(data), 32).unwrap();
+
+        assert_eq!(
+            "Name,Description\na,one\n",
+            String::from_utf8(chunker.next_chunk().unwrap().unwrap()).unwrap()
+        );
+        assert_eq!(
+            "Name,Description\nb,\"two\nlines\"\n",
+            String::from_utf8(chunker.next_chunk().unwrap().unwrap()).unwrap()
+        );
+        assert_eq!(
+            "Name,Description\nc,three\n",
+            String::from_utf8(chunker.next_chunk().unwrap().unwrap()).unwrap()
+        );
+        assert_eq!(None, chunker.next_chunk().unwrap());
+    }
+
+    #[test]
+    fn test_runs_ingest_job() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let create_mock = bulk_mock("POST", "/jobs/ingest", 200, job_info("750R", "Open").as_str());
+        let mut upload_mock = mock("PUT", "/services/data/vXY.Z/jobs/ingest/750R/batches");
+        upload_mock.with_status(201).with_body("").match_header(
+            "content-type",
+            "text/csv",
+        );
+        upload_mock.create();
+        let close_mock = bulk_mock(
+            "PATCH",
+            "/jobs/ingest/750R",
+            200,
+            job_info("750R", "UploadComplete").as_str(),
+        );
+
+        let job = create_ingest_job(&request, &IngestJobRequest::new("Account", Operation::Insert))
+            .unwrap();
+        let uploaded = upload_job_data(&request, job.id.as_str(), "Name\nTest\n".to_owned());
+        let closed = close_ingest_job(&request, job.id.as_str()).unwrap();
+
+        create_mock.remove();
+        upload_mock.remove();
+        close_mock.remove();
+
+        assert_eq!(JobState::Open, job.state);
+        assert!(uploaded.is_ok());
+        assert_eq!(JobState::UploadComplete, closed.state);
+    }
+
+    #[test]
+    fn test_streams_results() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let csv = "\"sf__Id\",\"sf__Created\",\"Name\"\n\"001R\",\"true\",\"Test\"\n";
+        let mock = bulk_mock("GET", "/jobs/ingest/750S/successfulResults/", 200, csv);
+        let mut output = vec![];
+
+        let written = ingest_results(&request, "750S", IngestResults::Successful, &mut output);
+
+        mock.remove();
+
+        assert_eq!(csv.len() as u64, written.unwrap());
+        assert_eq!(csv, String::from_utf8(output).unwrap());
+    }
+}
@@ -0,0 +1,171 @@
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use rest::{RestError, RestResult};
+use streaming::StreamingEvent;
+
+/// The channel carrying change events for every entity selected for Change Data Capture
+pub static ALL_CHANGE_EVENTS: &'static str = "/data/ChangeEvents";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ChangeType {
+    #[serde(rename = "CREATE")]
+    Create,
+    #[serde(rename = "UPDATE")]
+    Update,
+    #[serde(rename = "DELETE")]
+    Delete,
+    #[serde(rename = "UNDELETE")]
+    Undelete,
+    #[serde(rename = "GAP_CREATE")]
+    GapCreate,
+    #[serde(rename = "GAP_UPDATE")]
+    GapUpdate,
+    #[serde(rename = "GAP_DELETE")]
+    GapDelete,
+    #[serde(rename = "GAP_UNDELETE")]
+    GapUndelete,
+    #[serde(rename = "GAP_OVERFLOW")]
+    GapOverflow,
+}
+
+impl ChangeType {
+    /// Gap events carry no field values, the records need to be retrieved to see the change
+    pub fn is_gap(&self) -> bool {
+        match *self {
+            ChangeType::GapCreate |
+            ChangeType::GapUpdate |
+            ChangeType::GapDelete |
+            ChangeType::GapUndelete |
+            ChangeType::GapOverflow => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEventHeader {
+    pub entity_name: String,
+    pub record_ids: Vec<String>,
+    pub change_type: ChangeType,
+    #[serde(default)]
+    pub change_origin: String,
+    #[serde(default)]
+    pub transaction_key: String,
+    #[serde(default)]
+    pub sequence_number: u64,
+    #[serde(default)]
+    pub commit_timestamp: u64,
+    #[serde(default)]
+    pub commit_number: u64,
+    #[serde(default)]
+    pub commit_user: String,
+    #[serde(default)]
+    pub changed_fields: Vec<String>,
+    #[serde(default)]
+    pub nulled_fields: Vec<String>,
+    #[serde(default)]
+    pub diff_fields: Vec<String>,
+}
+
+/// A decoded change event, `fields` holds the new values of the changed fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeEvent<T = Value> {
+    pub channel: String,
+    pub replay_id: Option<i64>,
+    pub header: ChangeEventHeader,
+    pub fields: T,
+}
+
+impl<T: DeserializeOwned> ChangeEvent<T> {
+    pub fn from_event(event: &StreamingEvent) -> RestResult<ChangeEvent<T>> {
+        let mut payload = match event.body() {
+            Some(&Value::Object(ref payload)) => payload.clone(),
+            _ => return Err(RestError::ResponseParseFailure),
+        };
+
+        let header = payload
+            .remove("ChangeEventHeader")
+            .ok_or(RestError::ResponseParseFailure)
+            .and_then(|header| {
+                serde_json::from_value(header).map_err(|_| RestError::ResponseParseFailure)
+            })?;
+        let fields = serde_json::from_value(Value::Object(payload)).map_err(
+            |_| RestError::ResponseParseFailure,
+        )?;
+
+        Ok(ChangeEvent {
+            channel: event.channel.clone(),
+            replay_id: event.replay_id,
+            header: header,
+            fields: fields,
+        })
+    }
+}
+
+/// The change event channel of a single sObject, such as `/data/AccountChangeEvent` or
+/// `/data/Invoice__ChangeEvent` for the custom object `Invoice__c`
+pub fn change_channel(sobject: &str) -> String {
+    let name = if sobject.ends_with("__c") {
+        sobject[..sobject.len() - 1].to_owned()
+    } else {
+        sobject.to_owned()
+    };
+
+    "/data/".to_owned() + name.as_str() + "ChangeEvent"
+}
+
+#[cfg(test)]
+mod tests {
+    use cdc::{ChangeEvent, ChangeType, change_channel};
+    use streaming::StreamingEvent;
+
+    #[derive(Debug, Deserialize)]
+    struct AccountChange {
+        #[serde(rename = "Name")]
+        name: Option<String>,
+    }
+
+    #[test]
+    fn test_names_change_channels() {
+        assert_eq!("/data/AccountChangeEvent", change_channel("Account"));
+        assert_eq!("/data/Invoice__ChangeEvent", change_channel("Invoice__c"));
+    }
+
+    #[test]
+    fn test_decodes_change_event() {
+        let event = StreamingEvent {
+            channel: "/data/AccountChangeEvent".to_owned(),
+            replay_id: Some(4),
+            data: json!({
+                "schema": "IeRuaY6cbI_HsV8Rv1Mc5g",
+                "payload": {
+                    "ChangeEventHeader": {
+                        "entityName": "Account",
+                        "recordIds": ["001R0000004pMVkIAM"],
+                        "changeType": "UPDATE",
+                        "changeOrigin": "com/salesforce/api/rest/41.0",
+                        "transactionKey": "0002343d-9d90-e395-ed20-cf416ba652ad",
+                        "sequenceNumber": 1,
+                        "commitTimestamp": 1511396467000u64,
+                        "commitNumber": 10582220002u64,
+                        "commitUser": "005R0000000Hv1AIAS",
+                        "changedFields": ["Name", "LastModifiedDate"]
+                    },
+                    "Name": "Changed",
+                    "LastModifiedDate": "2017-11-23T00:21:07.000Z"
+                },
+                "event": {"replayId": 4}
+            }),
+        };
+
+        let change = ChangeEvent::<AccountChange>::from_event(&event).unwrap();
+
+        assert_eq!(ChangeType::Update, change.header.change_type);
+        assert!(!change.header.change_type.is_gap());
+        assert_eq!(vec!["001R0000004pMVkIAM"], change.header.record_ids);
+        assert_eq!(vec!["Name", "LastModifiedDate"], change.header.changed_fields);
+        assert_eq!(Some("Changed".to_owned()), change.fields.name);
+    }
+}
@@ -11,7 +11,9 @@ use self::micro_sf_client::SFClientError;
 pub enum CLIError {
     InvalidConfig,
     ConfigStorageFailure(io::Error),
+    FileAccess(io::Error),
     Format(serde_json::error::Error),
+    Import(String),
     Network(SFClientError),
 }
 
@@ -26,7 +28,9 @@ impl fmt::Display for CLIError {
                 )
             }
             CLIError::ConfigStorageFailure(ref err) => err.fmt(f),
+            CLIError::FileAccess(ref err) => err.fmt(f),
             CLIError::Format(_) => write!(f, "Failure to format response."),
+            CLIError::Import(ref reason) => write!(f, "{}", reason),
             CLIError::Network(ref err) => err.fmt(f),
         }
     }
@@ -40,7 +44,9 @@ impl Error for CLIError {
                  missing property."
             }
             CLIError::ConfigStorageFailure(ref err) => err.description(),
+            CLIError::FileAccess(ref err) => err.description(),
             CLIError::Format(_) => "Unable to format the response from the server.",
+            CLIError::Import(ref reason) => reason.as_str(),
             CLIError::Network(ref err) => err.description(),
         }
     }
@@ -49,7 +55,9 @@ impl Error for CLIError {
         match *self {
             CLIError::InvalidConfig => None,
             CLIError::ConfigStorageFailure(ref err) => Some(err),
+            CLIError::FileAccess(ref err) => Some(err),
             CLIError::Format(ref err) => Some(err),
+            CLIError::Import(_) => None,
             CLIError::Network(ref err) => Some(err),
         }
     }
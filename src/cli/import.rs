@@ -0,0 +1,67 @@
+extern crate micro_sf_client;
+extern crate serde_json;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::Path;
+
+use self::micro_sf_client::{PlanEntry, SFClient, TreeFile, resolve_references};
+
+use error::CLIError;
+
+/// Imports the record trees listed in an SFDX data plan. Files are resolved relative to the plan,
+/// and references saved by earlier entries are substituted into later ones.
+pub fn import_plan(client: &mut SFClient, plan_path: &str) -> Result<(), CLIError> {
+    let plan_path = Path::new(plan_path);
+    let directory = plan_path.parent().unwrap_or_else(|| Path::new("."));
+    let plan: Vec<PlanEntry> =
+        serde_json::from_reader(File::open(plan_path).map_err(CLIError::FileAccess)?)?;
+    let mut trees = vec![];
+    let mut declared = HashSet::new();
+
+    for entry in &plan {
+        for file_name in &entry.files {
+            let file = File::open(directory.join(file_name)).map_err(CLIError::FileAccess)?;
+            let tree: TreeFile = serde_json::from_reader(file)?;
+            declared.extend(tree.reference_ids());
+            trees.push((entry, file_name, tree));
+        }
+    }
+
+    let mut references = HashMap::new();
+
+    for (entry, file_name, mut tree) in trees {
+        if entry.resolve_refs {
+            for record in tree.records.iter_mut() {
+                resolve_references(record, &declared, &references).map_err(|err| {
+                    CLIError::Import(format!("Failed to import {}: {}", file_name, err))
+                })?;
+            }
+        }
+
+        let response = client.import_tree(entry.sobject.as_str(), &tree.records)?;
+
+        if response.has_errors {
+            for result in response.results.iter().filter(|result| !result.errors.is_empty()) {
+                for error in &result.errors {
+                    println!("{}: {} {}", result.reference_id, error.status_code, error.message);
+                }
+            }
+
+            return Err(CLIError::Import(format!("Failed to import {}", file_name)));
+        }
+
+        println!(
+            "Imported {} {} records from {}",
+            response.results.len(),
+            entry.sobject,
+            file_name
+        );
+
+        if entry.save_refs {
+            references.extend(response.references());
+        }
+    }
+
+    Ok(())
+}
@@ -7,10 +7,13 @@ extern crate structopt_derive;
 
 mod error;
 mod config;
+mod import;
+mod publish;
 
 use structopt::StructOpt;
 
 use config::Config;
+use error::CLIError;
 use micro_sf_client::SFClient;
 
 #[derive(StructOpt, Debug)]
@@ -20,14 +23,94 @@ struct Options {
     #[structopt(short = "c", long = "config", help = "Path to config file")]
     config: String,
 
-    /// A query to run against the SalesForce API
+    /// A query to run against the SalesForce API, as it was passed before subcommands existed
     #[structopt(short = "q", long = "query", help = "Query to run against the API")]
-    query: String,
+    query: Option<String>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// A query to run against the SalesForce API
+    #[structopt(name = "query", about = "Run a query against the API")]
+    Query {
+        #[structopt(help = "Query to run against the API")]
+        query: String,
+    },
+
+    /// A SOSL search to run, with results listed by sObject type
+    #[structopt(name = "search", about = "Run a SOSL search against the API")]
+    Search {
+        #[structopt(help = "SOSL statement to run, such as \"FIND {Acme} RETURNING Account\"")]
+        sosl: String,
+    },
+
+    /// Reports the org's limits so that headroom can be checked before large jobs
+    #[structopt(name = "limits", about = "Show the org limits and their remaining allowance")]
+    Limits {},
+
+    /// Seeds an org from an SFDX data plan and the record tree files it lists
+    #[structopt(name = "import-tree", about = "Import record trees listed in an SFDX data plan")]
+    ImportTree {
+        #[structopt(help = "Path to the plan file")]
+        plan: String,
+    },
+
+    /// Publishes the platform events read from stdin, either one json object or an array of them
+    #[structopt(name = "publish", about = "Publish platform events read as json from stdin")]
+    Publish {
+        #[structopt(help = "Platform event type, such as Order_Shipped__e")]
+        event_type: String,
+    },
+}
+
+fn run(client: &mut SFClient, command: &Command) -> Result<(), CLIError> {
+    match *command {
+        Command::Query { ref query } => {
+            let response = client.query(query.as_str())?;
+            println!("{:?}", response);
+        }
+        Command::Search { ref sosl } => {
+            let response = client.search(sosl.as_str())?;
+
+            for (sobject, records) in response.grouped() {
+                println!("{} ({})", sobject, records.len());
+
+                for record in records {
+                    println!("  {}", record);
+                }
+            }
+        }
+        Command::Limits {} => {
+            let limits = client.limits()?;
+
+            for (name, limit) in limits.iter() {
+                println!("{}: {} of {} remaining", name, limit.remaining, limit.max);
+            }
+        }
+        Command::ImportTree { ref plan } => import::import_plan(client, plan.as_str())?,
+        Command::Publish { ref event_type } => {
+            publish::publish_stdin(client, event_type.as_str())?
+        }
+    };
+
+    Ok(())
 }
 
 fn main() {
     let options = Options::from_args();
 
+    let command = match (options.command, options.query) {
+        (Some(command), _) => command,
+        (None, Some(query)) => Command::Query { query: query },
+        (None, None) => {
+            println!("Either --query or a subcommand is required, see --help");
+            return;
+        }
+    };
+
     Config::parse_config(options.config.as_str()).and_then(|c| {
         let create_client = SFClient::new(
             c.login_url,
@@ -41,13 +124,9 @@ fn main() {
         match create_client {
             Ok(mut client) => {
                 client.set_attempt_limit(1);
-                let res = client.query(options.query.as_str()).map_err(
-                    error::CLIError::Network,
-                );
 
-                match res {
-                    Ok(response) => println!("{:?}", response),
-                    Err(err) => println!("{}", err),
+                if let Err(err) = run(&mut client, &command) {
+                    println!("{}", err);
                 }
             }
             Err(error) => println!("{}", error),
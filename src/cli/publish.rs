@@ -0,0 +1,45 @@
+extern crate micro_sf_client;
+extern crate serde_json;
+
+use std::io;
+
+use self::micro_sf_client::SFClient;
+use self::serde_json::Value;
+
+use error::CLIError;
+
+/// Publishes the json read from stdin as events of `event_type`. An array is published as a batch
+/// and every result is printed alongside the position of its event.
+pub fn publish_stdin(client: &mut SFClient, event_type: &str) -> Result<(), CLIError> {
+    let input: Value = serde_json::from_reader(io::stdin())?;
+
+    let events = match input {
+        Value::Array(events) => events,
+        event => vec![event],
+    };
+
+    let (results, failure) = match client.publish_events(event_type, &events) {
+        Ok(results) => (results, None),
+        Err(err) => (err.completed, Some(err.error)),
+    };
+
+    for (index, result) in results.iter().enumerate() {
+        if result.success {
+            println!(
+                "{}: published {} as event {}",
+                index,
+                result.id.as_ref().map_or("", |id| id.as_str()),
+                result.event_uuid.as_ref().map_or("", |uuid| uuid.as_str())
+            );
+        } else {
+            for error in &result.errors {
+                println!("{}: {} {}", index, error.status_code, error.message);
+            }
+        }
+    }
+
+    match failure {
+        Some(err) => Err(CLIError::Network(err)),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,212 @@
+use reqwest::Method;
+use reqwest::header::Headers;
+use serde::Serialize;
+use serde_json::{self, Value};
+
+use rest::{RestError, RestRequest, RestResult, encode_segment, parse_response};
+use sobject::SaveResult;
+
+/// The most records a single sObject Collections call accepts
+pub const COLLECTION_LIMIT: usize = 200;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CollectionRequest {
+    all_or_none: bool,
+    records: Vec<Value>,
+}
+
+/// Serializes each record and tags it with the `attributes.type` the collection calls require
+pub fn typed_records<T: Serialize>(sobject: &str, records: &[T]) -> RestResult<Vec<Value>> {
+    records
+        .iter()
+        .map(|record| {
+            let mut value = serde_json::to_value(record).map_err(|err| {
+                RestError::InvalidRequest(err.to_string())
+            })?;
+
+            match value {
+                Value::Object(ref mut fields) => {
+                    fields.insert("attributes".to_owned(), json!({ "type": sobject }));
+                }
+                _ => {
+                    return Err(RestError::InvalidRequest(
+                        "Collection records must serialize to json objects".to_owned(),
+                    ))
+                }
+            };
+
+            Ok(value)
+        })
+        .collect()
+}
+
+fn check_size(count: usize) -> RestResult<()> {
+    if count > COLLECTION_LIMIT {
+        Err(RestError::InvalidRequest(format!(
+            "A collection call accepts at most {} records, {} were given",
+            COLLECTION_LIMIT,
+            count
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn send_records<T: Serialize>(
+    request: &RestRequest,
+    method: Method,
+    path: &str,
+    sobject: &str,
+    records: &[T],
+    all_or_none: bool,
+) -> RestResult<Vec<SaveResult>> {
+    check_size(records.len())?;
+
+    let body = CollectionRequest {
+        all_or_none: all_or_none,
+        records: typed_records(sobject, records)?,
+    };
+
+    request.send_json(method, request.url(path).as_str(), &body)
+}
+
+/// Creates up to 200 records, returning one result per record in input order
+pub fn create<T: Serialize>(
+    request: &RestRequest,
+    sobject: &str,
+    records: &[T],
+    all_or_none: bool,
+) -> RestResult<Vec<SaveResult>> {
+    send_records(request, Method::Post, "/composite/sobjects", sobject, records, all_or_none)
+}
+
+/// Updates up to 200 records, each of which must carry its `Id`
+pub fn update<T: Serialize>(
+    request: &RestRequest,
+    sobject: &str,
+    records: &[T],
+    all_or_none: bool,
+) -> RestResult<Vec<SaveResult>> {
+    send_records(request, Method::Patch, "/composite/sobjects", sobject, records, all_or_none)
+}
+
+/// Upserts up to 200 records matched on the `field` external id
+pub fn upsert<T: Serialize>(
+    request: &RestRequest,
+    sobject: &str,
+    field: &str,
+    records: &[T],
+    all_or_none: bool,
+) -> RestResult<Vec<SaveResult>> {
+    let path = "/composite/sobjects/".to_owned() + encode_segment(sobject).as_str() + "/" +
+        encode_segment(field).as_str();
+
+    send_records(request, Method::Patch, path.as_str(), sobject, records, all_or_none)
+}
+
+pub fn delete(request: &RestRequest, ids: &[&str], all_or_none: bool) -> RestResult<Vec<SaveResult>> {
+    check_size(ids.len())?;
+
+    let path = format!(
+        "/composite/sobjects?ids={}&allOrNone={}",
+        ids.join(","),
+        all_or_none
+    );
+
+    request
+        .send(
+            Method::Delete,
+            request.url(path.as_str()).as_str(),
+            Headers::new(),
+            |builder| builder,
+        )
+        .and_then(parse_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::{mock, Mock};
+
+    use collections::{create, delete, typed_records};
+    use http::HttpClient;
+    use rest::{RestError, RestRequest};
+
+    fn collection_mock(method: &str, path: &str, body: &str) -> Mock {
+        let mut m = mock(method, ("/services/data/vXY.Z".to_owned() + path).as_str());
+        m.with_status(200).with_body(body);
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_tags_records_with_type() {
+        let records = typed_records("Account", &[json!({"Name": "Test"})]).unwrap();
+
+        assert_eq!(
+            vec![json!({"attributes": {"type": "Account"}, "Name": "Test"})],
+            records
+        );
+    }
+
+    #[test]
+    fn test_reports_results_in_order() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!([
+            {"id": "001RM000003oLnnYAE", "success": true, "errors": []},
+            {"success": false, "errors": [{
+                "statusCode": "REQUIRED_FIELD_MISSING",
+                "message": "Required fields are missing: [Name]",
+                "fields": ["Name"]
+            }]}
+        ]);
+        let mock = collection_mock("POST", "/composite/sobjects", body.to_string().as_str());
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let results = create(
+            &request,
+            "Account",
+            &[json!({"Name": "Test"}), json!({})],
+            false,
+        ).unwrap();
+
+        mock.remove();
+
+        assert!(results[0].success);
+        assert_eq!(Some("001RM000003oLnnYAE".to_owned()), results[0].id);
+        assert!(!results[1].success);
+        assert_eq!("REQUIRED_FIELD_MISSING", results[1].errors[0].status_code);
+    }
+
+    #[test]
+    fn test_deletes_by_ids() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let mock = collection_mock(
+            "DELETE",
+            "/composite/sobjects?ids=001A,001B&allOrNone=true",
+            r#"[{"id": "001A", "success": true}, {"id": "001B", "success": true}]"#,
+        );
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let results = delete(&request, &["001A", "001B"], true).unwrap();
+
+        mock.remove();
+
+        assert_eq!(2, results.len());
+    }
+
+    #[test]
+    fn test_rejects_oversized_collections() {
+        let client = HttpClient::new().unwrap();
+        let request = RestRequest::new("http://127.0.0.1/", "vXY.Z", "test-token", &client);
+        let records = vec![json!({}); 201];
+
+        match create(&request, "Account", &records, false) {
+            Err(RestError::InvalidRequest(_)) => (),
+            _ => panic!("Failed to reject more than 200 records"),
+        };
+    }
+}
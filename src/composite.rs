@@ -0,0 +1,655 @@
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use std::collections::HashMap;
+
+use query::API_BASE;
+use rest::{ApiFailure, RestError, RestRequest, RestResult, encode_query};
+use sobject::{record_path, sobject_path};
+
+/// The most subrequests a single composite call accepts
+pub const COMPOSITE_LIMIT: usize = 25;
+
+/// The most subrequests a single batch call accepts
+pub const BATCH_LIMIT: usize = 25;
+
+/// The most subrequests a single graph may hold
+pub const GRAPH_NODE_LIMIT: usize = 500;
+
+/// A single call within a composite request. Paths are relative to the versioned API base, e.g.
+/// `/sobjects/Account`, and may refer to earlier results with `@{referenceId.field}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subrequest {
+    method: String,
+    path: String,
+    reference_id: String,
+    body: Option<Value>,
+    headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WireSubrequest<'a> {
+    method: &'a str,
+    url: String,
+    reference_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a Value>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    http_headers: &'a HashMap<String, String>,
+}
+
+impl Subrequest {
+    pub fn new<S: Into<String>>(method: &str, reference_id: S, path: S, body: Option<Value>) -> Subrequest {
+        Subrequest {
+            method: method.to_owned(),
+            path: path.into(),
+            reference_id: reference_id.into(),
+            body: body,
+            headers: HashMap::new(),
+        }
+    }
+
+    pub fn get<S: Into<String>>(reference_id: S, path: S) -> Subrequest {
+        Subrequest::new("GET", reference_id, path, None)
+    }
+
+    pub fn create<S: Into<String>>(reference_id: S, sobject: &str, record: Value) -> Subrequest {
+        Subrequest::new("POST", reference_id.into(), sobject_path(sobject), Some(record))
+    }
+
+    pub fn update<S: Into<String>>(reference_id: S, sobject: &str, id: &str, record: Value) -> Subrequest {
+        Subrequest::new(
+            "PATCH",
+            reference_id.into(),
+            record_path(sobject, id),
+            Some(record),
+        )
+    }
+
+    pub fn delete<S: Into<String>>(reference_id: S, sobject: &str, id: &str) -> Subrequest {
+        Subrequest::new("DELETE", reference_id.into(), record_path(sobject, id), None)
+    }
+
+    pub fn query<S: Into<String>>(reference_id: S, query: &str) -> Subrequest {
+        Subrequest::new(
+            "GET",
+            reference_id.into(),
+            "/query?q=".to_owned() + encode_query(query).as_str(),
+            None,
+        )
+    }
+
+    pub fn set_header<S: Into<String>>(&mut self, name: S, value: S) {
+        self.headers.insert(name.into(), value.into());
+    }
+
+    pub fn reference_id(&self) -> &str {
+        self.reference_id.as_str()
+    }
+
+    /// A reference to `field` of this subrequest's result, for use in later subrequests
+    pub fn reference(&self, field: &str) -> String {
+        format!("@{{{}.{}}}", self.reference_id, field)
+    }
+
+    fn wire(&self, version: &str) -> WireSubrequest {
+        WireSubrequest {
+            method: self.method.as_str(),
+            url: "/".to_owned() + API_BASE + version + self.path.as_str(),
+            reference_id: self.reference_id.as_str(),
+            body: self.body.as_ref(),
+            http_headers: &self.headers,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompositeRequest {
+    all_or_none: bool,
+    collate_subrequests: bool,
+    subrequests: Vec<Subrequest>,
+}
+
+impl CompositeRequest {
+    pub fn new(all_or_none: bool) -> CompositeRequest {
+        CompositeRequest {
+            all_or_none: all_or_none,
+            collate_subrequests: false,
+            subrequests: vec![],
+        }
+    }
+
+    pub fn set_collate_subrequests(&mut self, collate: bool) {
+        self.collate_subrequests = collate;
+    }
+
+    pub fn push(&mut self, subrequest: Subrequest) {
+        self.subrequests.push(subrequest);
+    }
+
+    pub fn subrequests(&self) -> &[Subrequest] {
+        self.subrequests.as_slice()
+    }
+
+    pub fn body(&self, version: &str) -> Value {
+        let subrequests: Vec<WireSubrequest> = self.subrequests
+            .iter()
+            .map(|subrequest| subrequest.wire(version))
+            .collect();
+
+        json!({
+            "allOrNone": self.all_or_none,
+            "collateSubrequests": self.collate_subrequests,
+            "compositeRequest": subrequests,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompositeResponse {
+    pub composite_response: Vec<Subresponse>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Subresponse {
+    pub body: Value,
+    pub http_headers: HashMap<String, String>,
+    pub http_status_code: u16,
+    pub reference_id: String,
+}
+
+impl CompositeResponse {
+    pub fn get(&self, reference_id: &str) -> Option<&Subresponse> {
+        self.composite_response.iter().find(|response| {
+            response.reference_id == reference_id
+        })
+    }
+}
+
+impl Subresponse {
+    pub fn is_success(&self) -> bool {
+        self.http_status_code >= 200 && self.http_status_code < 300
+    }
+
+    pub fn body_as<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_value(self.body.clone())
+    }
+
+    /// The errors reported by a failed subrequest, empty when it succeeded
+    pub fn errors(&self) -> Vec<ApiFailure> {
+        if self.is_success() {
+            vec![]
+        } else {
+            self.body_as::<Vec<ApiFailure>>().unwrap_or_default()
+        }
+    }
+}
+
+/// A set of subrequests that succeed or fail together within a graph call
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Graph {
+    graph_id: String,
+    subrequests: Vec<Subrequest>,
+}
+
+impl Graph {
+    pub fn new<S: Into<String>>(graph_id: S) -> Graph {
+        Graph {
+            graph_id: graph_id.into(),
+            subrequests: vec![],
+        }
+    }
+
+    pub fn push(&mut self, subrequest: Subrequest) {
+        self.subrequests.push(subrequest);
+    }
+
+    pub fn graph_id(&self) -> &str {
+        self.graph_id.as_str()
+    }
+
+    fn body(&self, version: &str) -> Value {
+        let subrequests: Vec<WireSubrequest> = self.subrequests
+            .iter()
+            .map(|subrequest| subrequest.wire(version))
+            .collect();
+
+        json!({
+            "graphId": self.graph_id,
+            "compositeRequest": subrequests,
+        })
+    }
+}
+
+/// Several independent graphs sent in one call, each applied atomically on its own
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphRequest {
+    graphs: Vec<Graph>,
+}
+
+impl GraphRequest {
+    pub fn new() -> GraphRequest {
+        GraphRequest::default()
+    }
+
+    pub fn push(&mut self, graph: Graph) {
+        self.graphs.push(graph);
+    }
+
+    pub fn graphs(&self) -> &[Graph] {
+        self.graphs.as_slice()
+    }
+
+    pub fn body(&self, version: &str) -> Value {
+        let graphs: Vec<Value> = self.graphs.iter().map(|graph| graph.body(version)).collect();
+
+        json!({ "graphs": graphs })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GraphResponse {
+    pub graphs: Vec<GraphResult>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GraphResult {
+    pub graph_id: String,
+    pub graph_response: CompositeResponse,
+    pub is_successful: bool,
+}
+
+impl GraphResponse {
+    pub fn get(&self, graph_id: &str) -> Option<&GraphResult> {
+        self.graphs.iter().find(|graph| graph.graph_id == graph_id)
+    }
+}
+
+impl GraphResult {
+    /// The result of a single node of the graph
+    pub fn node(&self, reference_id: &str) -> Option<&Subresponse> {
+        self.graph_response.get(reference_id)
+    }
+}
+
+/// An independent call within a batch request, paths are relative to the versioned API base
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchSubrequest {
+    method: String,
+    path: String,
+    body: Option<Value>,
+}
+
+impl BatchSubrequest {
+    pub fn new<S: Into<String>>(method: &str, path: S, body: Option<Value>) -> BatchSubrequest {
+        BatchSubrequest {
+            method: method.to_owned(),
+            path: path.into(),
+            body: body,
+        }
+    }
+
+    pub fn get<S: Into<String>>(path: S) -> BatchSubrequest {
+        BatchSubrequest::new("GET", path, None)
+    }
+
+    pub fn query(query: &str) -> BatchSubrequest {
+        BatchSubrequest::get("/query?q=".to_owned() + encode_query(query).as_str())
+    }
+
+    pub fn create(sobject: &str, record: Value) -> BatchSubrequest {
+        BatchSubrequest::new("POST", sobject_path(sobject), Some(record))
+    }
+
+    pub fn update(sobject: &str, id: &str, record: Value) -> BatchSubrequest {
+        BatchSubrequest::new("PATCH", record_path(sobject, id), Some(record))
+    }
+
+    pub fn delete(sobject: &str, id: &str) -> BatchSubrequest {
+        BatchSubrequest::new("DELETE", record_path(sobject, id), None)
+    }
+
+    fn body(&self, version: &str) -> Value {
+        let mut body = json!({
+            "method": self.method,
+            "url": version.to_owned() + self.path.as_str(),
+        });
+
+        if let Some(ref input) = self.body {
+            body["richInput"] = input.clone();
+        }
+
+        body
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchRequest {
+    halt_on_error: bool,
+    subrequests: Vec<BatchSubrequest>,
+}
+
+impl BatchRequest {
+    pub fn new(halt_on_error: bool) -> BatchRequest {
+        BatchRequest {
+            halt_on_error: halt_on_error,
+            subrequests: vec![],
+        }
+    }
+
+    pub fn push(&mut self, subrequest: BatchSubrequest) {
+        self.subrequests.push(subrequest);
+    }
+
+    pub fn subrequests(&self) -> &[BatchSubrequest] {
+        self.subrequests.as_slice()
+    }
+
+    pub fn body(&self, version: &str) -> Value {
+        let subrequests: Vec<Value> = self.subrequests
+            .iter()
+            .map(|subrequest| subrequest.body(version))
+            .collect();
+
+        json!({
+            "haltOnError": self.halt_on_error,
+            "batchRequests": subrequests,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BatchResponse {
+    pub has_errors: bool,
+    pub results: Vec<BatchResult>,
+}
+
+/// The outcome of one batch subrequest, in the same position as the subrequest was added
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BatchResult {
+    pub status_code: u16,
+    pub result: Value,
+}
+
+impl BatchResult {
+    pub fn is_success(&self) -> bool {
+        self.status_code >= 200 && self.status_code < 300
+    }
+
+    pub fn result_as<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_value(self.result.clone())
+    }
+
+    pub fn errors(&self) -> Vec<ApiFailure> {
+        if self.is_success() {
+            vec![]
+        } else {
+            self.result_as::<Vec<ApiFailure>>().unwrap_or_default()
+        }
+    }
+}
+
+pub fn check_subrequests(count: usize, limit: usize) -> RestResult<()> {
+    if count > limit {
+        Err(RestError::InvalidRequest(format!(
+            "A composite call accepts at most {} subrequests, {} were given",
+            limit,
+            count
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn send_composite(request: &RestRequest, composite: &CompositeRequest) -> RestResult<CompositeResponse> {
+    check_subrequests(composite.subrequests.len(), COMPOSITE_LIMIT)?;
+
+    request.send_json(
+        Method::Post,
+        request.url("/composite").as_str(),
+        &composite.body(request.version()),
+    )
+}
+
+pub fn send_graphs(request: &RestRequest, graphs: &GraphRequest) -> RestResult<GraphResponse> {
+    for graph in &graphs.graphs {
+        check_subrequests(graph.subrequests.len(), GRAPH_NODE_LIMIT)?;
+    }
+
+    request.send_json(
+        Method::Post,
+        request.url("/composite/graph").as_str(),
+        &graphs.body(request.version()),
+    )
+}
+
+pub fn send_batch(request: &RestRequest, batch: &BatchRequest) -> RestResult<BatchResponse> {
+    check_subrequests(batch.subrequests.len(), BATCH_LIMIT)?;
+
+    request.send_json(
+        Method::Post,
+        request.url("/composite/batch").as_str(),
+        &batch.body(request.version()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use composite::{BatchRequest, BatchSubrequest, CompositeRequest, Graph, GraphRequest, Subrequest,
+                    send_batch, send_composite, send_graphs};
+    use query::QueryResponse;
+    use http::HttpClient;
+    use rest::{RestError, RestRequest};
+    use sobject::SaveResult;
+
+    #[test]
+    fn test_builds_composite_body() {
+        let account = Subrequest::create("refAccount", "Account", json!({"Name": "Test"}));
+        let contact = Subrequest::create(
+            "refContact",
+            "Contact",
+            json!({"LastName": "Test", "AccountId": account.reference("id")}),
+        );
+        let mut composite = CompositeRequest::new(true);
+        composite.push(account);
+        composite.push(contact);
+        composite.push(Subrequest::query("refQuery", "SELECT Id FROM Contact"));
+
+        assert_eq!(
+            json!({
+                "allOrNone": true,
+                "collateSubrequests": false,
+                "compositeRequest": [
+                    {
+                        "method": "POST",
+                        "url": "/services/data/v41.0/sobjects/Account",
+                        "referenceId": "refAccount",
+                        "body": {"Name": "Test"}
+                    },
+                    {
+                        "method": "POST",
+                        "url": "/services/data/v41.0/sobjects/Contact",
+                        "referenceId": "refContact",
+                        "body": {"LastName": "Test", "AccountId": "@{refAccount.id}"}
+                    },
+                    {
+                        "method": "GET",
+                        "url": "/services/data/v41.0/query?q=SELECT+Id+FROM+Contact",
+                        "referenceId": "refQuery"
+                    }
+                ]
+            }),
+            composite.body("v41.0")
+        );
+    }
+
+    #[test]
+    fn test_parses_subresponses() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!({
+            "compositeResponse": [
+                {
+                    "body": {"id": "001R00000033I6AIAU", "success": true, "errors": []},
+                    "httpHeaders": {"Location": "/services/data/vXY.Z/sobjects/Account/001R00000033I6AIAU"},
+                    "httpStatusCode": 201,
+                    "referenceId": "refAccount"
+                },
+                {
+                    "body": [{"errorCode": "PROCESSING_HALTED", "message": "halted"}],
+                    "httpHeaders": {},
+                    "httpStatusCode": 400,
+                    "referenceId": "refContact"
+                }
+            ]
+        });
+        let mut m = mock("POST", "/services/data/vXY.Z/composite");
+        m.with_status(200).with_body(body.to_string().as_str());
+        m.create();
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut composite = CompositeRequest::new(false);
+        composite.push(Subrequest::create("refAccount", "Account", json!({})));
+
+        let response = send_composite(&request, &composite).unwrap();
+
+        m.remove();
+
+        let account = response.get("refAccount").unwrap();
+        assert!(account.is_success());
+        assert_eq!(
+            Some("001R00000033I6AIAU".to_owned()),
+            account.body_as::<SaveResult>().unwrap().id
+        );
+        assert_eq!(
+            "PROCESSING_HALTED",
+            response.get("refContact").unwrap().errors()[0].error_code
+        );
+    }
+
+    #[test]
+    fn test_rejects_too_many_subrequests() {
+        let client = HttpClient::new().unwrap();
+        let request = RestRequest::new("http://127.0.0.1/", "vXY.Z", "test-token", &client);
+        let mut composite = CompositeRequest::new(false);
+
+        for index in 0..26 {
+            composite.push(Subrequest::get(format!("ref{}", index), "/limits".to_owned()));
+        }
+
+        match send_composite(&request, &composite) {
+            Err(RestError::InvalidRequest(_)) => (),
+            _ => panic!("Failed to reject more than 25 subrequests"),
+        };
+    }
+
+    #[test]
+    fn test_reports_results_per_graph() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!({
+            "graphs": [
+                {
+                    "graphId": "order1",
+                    "graphResponse": {
+                        "compositeResponse": [{
+                            "body": {"id": "001R00000064wc7IAA", "success": true, "errors": []},
+                            "httpHeaders": {},
+                            "httpStatusCode": 201,
+                            "referenceId": "refAccount"
+                        }]
+                    },
+                    "isSuccessful": true
+                },
+                {
+                    "graphId": "order2",
+                    "graphResponse": {
+                        "compositeResponse": [{
+                            "body": [{"errorCode": "INVALID_FIELD", "message": "No such column"}],
+                            "httpHeaders": {},
+                            "httpStatusCode": 400,
+                            "referenceId": "refAccount"
+                        }]
+                    },
+                    "isSuccessful": false
+                }
+            ]
+        });
+        let mut m = mock("POST", "/services/data/vXY.Z/composite/graph");
+        m.with_status(200).with_body(body.to_string().as_str());
+        m.create();
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let mut graphs = GraphRequest::new();
+        for id in &["order1", "order2"] {
+            let mut graph = Graph::new(*id);
+            graph.push(Subrequest::create("refAccount", "Account", json!({})));
+            graphs.push(graph);
+        }
+
+        let response = send_graphs(&request, &graphs).unwrap();
+
+        m.remove();
+
+        assert!(response.get("order1").unwrap().is_successful);
+        assert!(response.get("order1").unwrap().node("refAccount").unwrap().is_success());
+        assert!(!response.get("order2").unwrap().is_successful);
+        assert_eq!(
+            "INVALID_FIELD",
+            response.get("order2").unwrap().node("refAccount").unwrap().errors()[0].error_code
+        );
+    }
+
+    #[test]
+    fn test_sends_independent_batch() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let mut batch = BatchRequest::new(true);
+        batch.push(BatchSubrequest::update("Account", "001D000000K0fXOIAZ", json!({"Name": "New"})));
+        batch.push(BatchSubrequest::query("SELECT Id FROM Account"));
+
+        assert_eq!(
+            json!({
+                "haltOnError": true,
+                "batchRequests": [
+                    {
+                        "method": "PATCH",
+                        "url": "vXY.Z/sobjects/Account/001D000000K0fXOIAZ",
+                        "richInput": {"Name": "New"}
+                    },
+                    {"method": "GET", "url": "vXY.Z/query?q=SELECT+Id+FROM+Account"}
+                ]
+            }),
+            batch.body("vXY.Z")
+        );
+
+        let body = json!({
+            "hasErrors": false,
+            "results": [
+                {"statusCode": 204, "result": null},
+                {"statusCode": 200, "result": {"total_size": 1, "done": true, "records": [{"id": "001D000000K0fXOIAZ"}]}}
+            ]
+        });
+        let mut m = mock("POST", "/services/data/vXY.Z/composite/batch");
+        m.with_status(200).with_body(body.to_string().as_str());
+        m.create();
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let response = send_batch(&request, &batch).unwrap();
+
+        m.remove();
+
+        assert!(!response.has_errors);
+        assert_eq!(204, response.results[0].status_code);
+        assert!(response.results[1].result_as::<QueryResponse>().is_ok());
+    }
+}
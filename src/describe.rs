@@ -0,0 +1,238 @@
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+use rest::{RestRequest, RestResult};
+use sobject::sobject_path;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DescribeGlobal {
+    pub encoding: String,
+    pub max_batch_size: u32,
+    pub sobjects: Vec<GlobalSObject>,
+}
+
+/// The summary of an sObject and its capabilities as listed by `describeGlobal`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GlobalSObject {
+    pub name: String,
+    pub label: String,
+    pub label_plural: String,
+    pub key_prefix: Option<String>,
+    pub custom: bool,
+    pub custom_setting: bool,
+    pub createable: bool,
+    pub updateable: bool,
+    pub deletable: bool,
+    pub undeletable: bool,
+    pub mergeable: bool,
+    pub queryable: bool,
+    pub searchable: bool,
+    pub retrieveable: bool,
+    pub replicateable: bool,
+    pub triggerable: bool,
+    pub layoutable: bool,
+    pub feed_enabled: bool,
+    pub urls: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SObjectDescribe {
+    pub name: String,
+    pub label: String,
+    pub label_plural: String,
+    pub key_prefix: Option<String>,
+    pub custom: bool,
+    pub createable: bool,
+    pub updateable: bool,
+    pub deletable: bool,
+    pub undeletable: bool,
+    pub queryable: bool,
+    pub searchable: bool,
+    pub retrieveable: bool,
+    pub replicateable: bool,
+    pub fields: Vec<Field>,
+    pub child_relationships: Vec<ChildRelationship>,
+    pub record_type_infos: Vec<RecordTypeInfo>,
+    pub urls: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Field {
+    pub name: String,
+    pub label: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    pub soap_type: String,
+    pub length: u32,
+    pub byte_length: u32,
+    pub precision: u32,
+    pub scale: u32,
+    pub digits: u32,
+    pub nillable: bool,
+    pub createable: bool,
+    pub updateable: bool,
+    pub unique: bool,
+    pub external_id: bool,
+    pub id_lookup: bool,
+    pub calculated: bool,
+    pub custom: bool,
+    pub default_value: Option<Value>,
+    pub defaulted_on_create: bool,
+    pub picklist_values: Vec<PicklistValue>,
+    pub dependent_picklist: bool,
+    pub controller_name: Option<String>,
+    pub reference_to: Vec<String>,
+    pub relationship_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PicklistValue {
+    pub active: bool,
+    pub default_value: bool,
+    pub label: Option<String>,
+    pub value: String,
+    pub valid_for: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChildRelationship {
+    #[serde(rename = "childSObject")]
+    pub child_sobject: String,
+    pub field: String,
+    pub relationship_name: Option<String>,
+    pub cascade_delete: bool,
+    pub restricted_delete: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RecordTypeInfo {
+    pub name: String,
+    pub developer_name: Option<String>,
+    pub record_type_id: Option<String>,
+    pub available: bool,
+    pub default_record_type_mapping: bool,
+    pub master: bool,
+}
+
+impl SObjectDescribe {
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+pub fn describe_path(sobject: &str) -> String {
+    sobject_path(sobject) + "/describe/"
+}
+
+pub fn describe_global(request: &RestRequest) -> RestResult<DescribeGlobal> {
+    request.get(request.url("/sobjects/").as_str())
+}
+
+pub fn describe(request: &RestRequest, sobject: &str) -> RestResult<SObjectDescribe> {
+    request.get(request.url(describe_path(sobject).as_str()).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::{mock, Mock};
+
+    use describe::{describe, describe_global};
+    use http::HttpClient;
+    use rest::RestRequest;
+
+    fn describe_mock(path: &str, body: String) -> Mock {
+        let mut m = mock("GET", ("/services/data/vXY.Z".to_owned() + path).as_str());
+        m.with_status(200).with_body(body.as_str());
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_parses_describe_global() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!({
+            "encoding": "UTF-8",
+            "maxBatchSize": 200,
+            "sobjects": [{
+                "name": "Account",
+                "label": "Account",
+                "keyPrefix": "001",
+                "createable": true,
+                "queryable": true,
+                "urls": {"sobject": "/services/data/vXY.Z/sobjects/Account"}
+            }]
+        });
+        let mock = describe_mock("/sobjects/", body.to_string());
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let global = describe_global(&request).unwrap();
+
+        mock.remove();
+
+        assert_eq!(200, global.max_batch_size);
+        assert_eq!("Account", global.sobjects[0].name);
+        assert_eq!(Some("001".to_owned()), global.sobjects[0].key_prefix);
+        assert!(global.sobjects[0].createable);
+        assert!(!global.sobjects[0].deletable);
+    }
+
+    #[test]
+    fn test_parses_sobject_describe() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!({
+            "name": "DescribeTest",
+            "createable": true,
+            "updateable": false,
+            "fields": [
+                {
+                    "name": "Industry",
+                    "type": "picklist",
+                    "nillable": true,
+                    "picklistValues": [
+                        {"active": true, "defaultValue": false, "label": "Banking", "value": "Banking"}
+                    ]
+                },
+                {
+                    "name": "ParentId",
+                    "type": "reference",
+                    "referenceTo": ["Account"],
+                    "relationshipName": "Parent"
+                }
+            ],
+            "childRelationships": [
+                {"childSObject": "Contact", "field": "AccountId", "relationshipName": "Contacts"}
+            ],
+            "recordTypeInfos": [
+                {"name": "Master", "recordTypeId": "012000000000000AAA", "available": true, "master": true}
+            ]
+        });
+        let mock = describe_mock("/sobjects/DescribeTest/describe/", body.to_string());
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let described = describe(&request, "DescribeTest").unwrap();
+
+        mock.remove();
+
+        assert!(described.createable);
+        assert!(!described.updateable);
+        assert_eq!("picklist", described.field("Industry").unwrap().field_type);
+        assert_eq!(
+            "Banking",
+            described.field("Industry").unwrap().picklist_values[0].value
+        );
+        assert_eq!(vec!["Account".to_owned()], described.field("ParentId").unwrap().reference_to);
+        assert_eq!("Contact", described.child_relationships[0].child_sobject);
+        assert!(described.record_type_infos[0].master);
+    }
+}
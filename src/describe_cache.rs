@@ -0,0 +1,203 @@
+use reqwest::{Method, StatusCode};
+use reqwest::header::Headers;
+use serde_json;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use describe::{SObjectDescribe, describe_path};
+use rest::{RestRequest, RestResult, header, parse_response};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedDescribe {
+    pub last_modified: String,
+    pub describe: SObjectDescribe,
+}
+
+/// Keeps describe results in memory, and optionally on disk, so that they can be revalidated with
+/// `If-Modified-Since` instead of being downloaded again
+#[derive(Debug, Default)]
+pub struct DescribeCache {
+    entries: HashMap<String, CachedDescribe>,
+    directory: Option<PathBuf>,
+}
+
+impl DescribeCache {
+    pub fn new() -> DescribeCache {
+        DescribeCache::default()
+    }
+
+    /// A cache that also persists entries as json files in `directory`, which is created if needed
+    pub fn with_directory<P: Into<PathBuf>>(directory: P) -> DescribeCache {
+        DescribeCache {
+            entries: HashMap::new(),
+            directory: Some(directory.into()),
+        }
+    }
+
+    pub fn key(instance_url: &str, version: &str, sobject: &str) -> String {
+        instance_url.trim_right_matches('/').to_owned() + "|" + version + "|" + sobject
+    }
+
+    fn file(&self, key: &str) -> Option<PathBuf> {
+        self.directory.as_ref().map(|directory| {
+            let name: String = key.chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect();
+            directory.join(name + ".json")
+        })
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<CachedDescribe> {
+        if let Some(entry) = self.entries.get(key) {
+            return Some(entry.clone());
+        }
+
+        let stored = self.file(key)
+            .and_then(|path| File::open(path).ok())
+            .and_then(|file| serde_json::from_reader::<_, CachedDescribe>(file).ok());
+
+        if let Some(ref entry) = stored {
+            self.entries.insert(key.to_owned(), entry.clone());
+        }
+
+        stored
+    }
+
+    pub fn insert(&mut self, key: &str, entry: CachedDescribe) {
+        if let Some(path) = self.file(key) {
+            let written = path.parent()
+                .map_or(Ok(()), |directory| fs::create_dir_all(directory))
+                .and_then(|_| File::create(&path))
+                .map_err(|err| err.to_string())
+                .and_then(|file| {
+                    serde_json::to_writer(file, &entry).map_err(|err| err.to_string())
+                });
+
+            if let Err(err) = written {
+                warn!("Failed to store describe in {}: {}", path.display(), err);
+            }
+        }
+
+        self.entries.insert(key.to_owned(), entry);
+    }
+
+    /// Forgets the in memory entries, files on disk are left in place
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Describes `sobject`, answering from `cache` when the API reports it has not changed
+pub fn cached_describe(
+    request: &RestRequest,
+    cache: &mut DescribeCache,
+    sobject: &str,
+) -> RestResult<SObjectDescribe> {
+    let key = DescribeCache::key(request.instance_url("").as_str(), request.version(), sobject);
+    let cached = cache.get(key.as_str());
+
+    let mut headers = Headers::new();
+
+    if let Some(ref entry) = cached {
+        headers.set_raw("If-Modified-Since", entry.last_modified.clone());
+    }
+
+    let response = request.send(
+        Method::Get,
+        request.url(describe_path(sobject).as_str()).as_str(),
+        headers,
+        |builder| builder,
+    )?;
+
+    if let (&StatusCode::NotModified, Some(entry)) = (response.status(), cached) {
+        debug!("Describe of {} has not changed, using cached copy", sobject);
+        return Ok(entry.describe);
+    }
+
+    let last_modified = header(&response, "Last-Modified").or_else(|| header(&response, "Date"));
+    let described = parse_response::<SObjectDescribe>(response)?;
+
+    if let Some(last_modified) = last_modified {
+        cache.insert(
+            key.as_str(),
+            CachedDescribe {
+                last_modified: last_modified,
+                describe: described.clone(),
+            },
+        );
+    }
+
+    Ok(described)
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use std::env;
+    use std::fs;
+
+    use describe::SObjectDescribe;
+    use describe_cache::{CachedDescribe, DescribeCache, cached_describe};
+    use http::HttpClient;
+    use rest::RestRequest;
+
+    const LAST_MODIFIED: &'static str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+    #[test]
+    fn test_revalidates_cached_describe() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let path = "/services/data/vXY.Z/sobjects/CacheTest/describe/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut cache = DescribeCache::new();
+
+        let mut fresh = mock("GET", path);
+        fresh
+            .with_status(200)
+            .with_header("Last-Modified", LAST_MODIFIED)
+            .with_body(r#"{"name": "CacheTest", "createable": true}"#);
+        fresh.create();
+
+        let first = cached_describe(&request, &mut cache, "CacheTest").unwrap();
+
+        fresh.remove();
+
+        let mut unchanged = mock("GET", path);
+        unchanged.with_status(304).with_body("").match_header(
+            "If-Modified-Since",
+            LAST_MODIFIED,
+        );
+        unchanged.create();
+
+        let second = cached_describe(&request, &mut cache, "CacheTest").unwrap();
+
+        unchanged.remove();
+
+        assert_eq!(first, second);
+        assert!(second.createable);
+    }
+
+    #[test]
+    fn test_persists_entries_to_disk() {
+        let directory = env::temp_dir().join("micro_sf_client_describe_cache_test");
+        let key = DescribeCache::key("https://na1.salesforce.com/", "v41.0", "Account");
+        let entry = CachedDescribe {
+            last_modified: LAST_MODIFIED.to_owned(),
+            describe: SObjectDescribe {
+                name: "Account".to_owned(),
+                ..SObjectDescribe::default()
+            },
+        };
+
+        DescribeCache::with_directory(directory.clone()).insert(key.as_str(), entry.clone());
+        let stored = DescribeCache::with_directory(directory.clone()).get(key.as_str());
+
+        let _ = fs::remove_dir_all(directory);
+
+        assert_eq!(Some(entry), stored);
+    }
+}
@@ -0,0 +1,124 @@
+use serde::Serialize;
+
+use collections;
+use rest::{ApiFailure, RestError, RestRequest, RestResult};
+use sobject::{self, SaveResult};
+
+/// The status code of the entry that carries a queued event's `EventUuid` in its message
+static OPERATION_ENQUEUED: &'static str = "OPERATION_ENQUEUED";
+
+/// The outcome of publishing a platform event. `id` identifies the publish operation, while
+/// `event_uuid` is the `EventUuid` subscribers will see on the event. The replay id is only
+/// assigned once the event is delivered.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PublishResult {
+    pub id: Option<String>,
+    pub success: bool,
+    pub event_uuid: Option<String>,
+    /// Failures to publish, without the `OPERATION_ENQUEUED` entry
+    pub errors: Vec<ApiFailure>,
+}
+
+impl From<SaveResult> for PublishResult {
+    fn from(result: SaveResult) -> PublishResult {
+        let (enqueued, errors): (Vec<ApiFailure>, Vec<ApiFailure>) = result
+            .errors
+            .into_iter()
+            .partition(|error| error.status_code == OPERATION_ENQUEUED);
+
+        PublishResult {
+            id: result.id,
+            success: result.success,
+            event_uuid: enqueued.into_iter().next().map(|error| error.message),
+            errors: errors,
+        }
+    }
+}
+
+fn check_event_type(event_type: &str) -> RestResult<()> {
+    if event_type.ends_with("__e") {
+        Ok(())
+    } else {
+        Err(RestError::InvalidRequest(
+            format!("{} is not a platform event type", event_type),
+        ))
+    }
+}
+
+/// Publishes a single event of `event_type`, such as `Order_Shipped__e`
+pub fn publish<T: Serialize>(
+    request: &RestRequest,
+    event_type: &str,
+    event: &T,
+) -> RestResult<PublishResult> {
+    check_event_type(event_type)?;
+    sobject::create(request, event_type, event).map(PublishResult::from)
+}
+
+/// Publishes up to 200 events in one call, returning one result per event in input order
+pub fn publish_batch<T: Serialize>(
+    request: &RestRequest,
+    event_type: &str,
+    events: &[T],
+) -> RestResult<Vec<PublishResult>> {
+    check_event_type(event_type)?;
+    collections::create(request, event_type, events, false).map(|results| {
+        results.into_iter().map(PublishResult::from).collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use events::{publish, publish_batch};
+    use http::HttpClient;
+    use rest::{RestError, RestRequest};
+
+    #[test]
+    fn test_publishes_event() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock("POST", "/services/data/vXY.Z/sobjects/Order_Shipped__e");
+        m.with_status(201).with_body(
+            json!({
+                "id": "e00xx000000000B",
+                "success": true,
+                "errors": [{
+                    "statusCode": "OPERATION_ENQUEUED",
+                    "message": "08d2d9cd-ce3a-4a8b-8a6c-b5d0e4bf0c8f",
+                    "fields": []
+                }]
+            }).to_string()
+                .as_str(),
+        );
+        m.create();
+
+        let result = publish(&request, "Order_Shipped__e", &json!({"Order_Number__c": "100"}));
+
+        m.remove();
+
+        let result = result.unwrap();
+        assert!(result.success);
+        assert_eq!(Some("e00xx000000000B".to_owned()), result.id);
+        assert_eq!(
+            Some("08d2d9cd-ce3a-4a8b-8a6c-b5d0e4bf0c8f".to_owned()),
+            result.event_uuid
+        );
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_non_event_types() {
+        let client = HttpClient::new().unwrap();
+        let request = RestRequest::new("http://127.0.0.1/", "vXY.Z", "test-token", &client);
+
+        match publish_batch(&request, "Account", &[json!({})]) {
+            Err(RestError::InvalidRequest(_)) => (),
+            _ => panic!("Failed to reject an sObject that is not a platform event"),
+        };
+    }
+}
@@ -0,0 +1,377 @@
+use reqwest::{Client, Error as ClientError, Method, RequestBuilder, Response, StatusCode};
+use reqwest::header::Headers;
+
+use std::cell::Cell;
+use std::fmt;
+use std::str;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rate_limit::RateLimiter;
+use usage::{ApiUsage, LIMIT_INFO_HEADER};
+
+/// A hook that is handed every request before it is sent and every response once it arrives.
+/// Both token and API calls pass through the chain, in the order interceptors were added.
+pub trait Interceptor: Send + Sync {
+    fn on_request(&self, _request: &mut OutgoingRequest) {}
+
+    fn on_response(&self, _response: &IncomingResponse) {}
+
+    /// Called when the rate limiter held a request back before it could be sent
+    fn on_throttle(&self, _request: &OutgoingRequest, _waited: Duration) {}
+}
+
+/// Headers that carry the access token and are redacted from `Debug` output
+const SECRET_HEADERS: [&'static str; 2] = ["authorization", "x-sfdc-session"];
+
+#[derive(Clone)]
+pub struct OutgoingRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: Headers,
+}
+
+impl fmt::Debug for OutgoingRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutgoingRequest")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("headers", &RedactedHeaders(&self.headers))
+            .finish()
+    }
+}
+
+pub struct IncomingResponse<'a> {
+    pub request: &'a OutgoingRequest,
+    pub status: StatusCode,
+    pub headers: &'a Headers,
+    pub elapsed: Duration,
+}
+
+impl<'a> fmt::Debug for IncomingResponse<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IncomingResponse")
+            .field("request", &self.request)
+            .field("status", &self.status)
+            .field("headers", &RedactedHeaders(self.headers))
+            .field("elapsed", &self.elapsed)
+            .finish()
+    }
+}
+
+struct RedactedHeaders<'a>(&'a Headers);
+
+impl<'a> fmt::Debug for RedactedHeaders<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut headers = f.debug_map();
+
+        for header in self.0.iter() {
+            if SECRET_HEADERS.contains(&header.name().to_lowercase().as_str()) {
+                headers.entry(&header.name(), &"[REDACTED]");
+            } else {
+                headers.entry(&header.name(), &header.value_string());
+            }
+        }
+
+        headers.finish()
+    }
+}
+
+pub struct HttpClient {
+    client: Client,
+    interceptors: Vec<Box<Interceptor>>,
+    usage: Cell<Option<ApiUsage>>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl fmt::Debug for HttpClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpClient")
+            .field("client", &self.client)
+            .field("interceptors", &self.interceptors.len())
+            .field("usage", &self.usage.get())
+            .field("limiter", &self.limiter)
+            .finish()
+    }
+}
+
+impl HttpClient {
+    pub fn new() -> Result<HttpClient, ClientError> {
+        Client::new().map(|client| {
+            HttpClient {
+                client: client,
+                interceptors: vec![],
+                usage: Cell::new(None),
+                limiter: None,
+            }
+        })
+    }
+
+    pub fn add_interceptor<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.interceptors.push(Box::new(interceptor));
+    }
+
+    pub fn set_rate_limiter(&mut self, limiter: Option<Arc<RateLimiter>>) {
+        self.limiter = limiter;
+    }
+
+    /// The most recent API usage reported by the instance, if any response has carried it
+    pub fn usage(&self) -> Option<ApiUsage> {
+        self.usage.get()
+    }
+
+    pub fn send<F>(
+        &self,
+        method: Method,
+        url: &str,
+        headers: Headers,
+        body: F,
+    ) -> Result<Response, ClientError>
+    where
+        F: FnOnce(RequestBuilder) -> RequestBuilder,
+    {
+        let mut request = OutgoingRequest {
+            method: method,
+            url: url.to_owned(),
+            headers: headers,
+        };
+
+        for interceptor in &self.interceptors {
+            interceptor.on_request(&mut request);
+        }
+
+        let _permit = self.limiter.as_ref().map(|limiter| {
+            let permit = limiter.acquire();
+
+            if let Some(waited) = permit.waited() {
+                debug!(
+                    "{} {} was throttled for {}ms",
+                    request.method,
+                    redact_url(request.url.as_str()),
+                    millis(waited)
+                );
+
+                for interceptor in &self.interceptors {
+                    interceptor.on_throttle(&request, waited);
+                }
+            }
+
+            permit
+        });
+
+        trace!("{} {}", request.method, redact_url(request.url.as_str()));
+
+        let started = Instant::now();
+        let builder = self.client
+            .request(request.method.clone(), request.url.as_str())
+            .headers(request.headers.clone());
+        let response = body(builder).send().map_err(|err| {
+            warn!(
+                "{} {} failed: {}",
+                request.method,
+                redact_url(request.url.as_str()),
+                err
+            );
+            err
+        })?;
+
+        {
+            let incoming = IncomingResponse {
+                request: &request,
+                status: *response.status(),
+                headers: response.headers(),
+                elapsed: started.elapsed(),
+            };
+
+            debug!(
+                "{} {} -> {} in {}ms",
+                request.method,
+                redact_url(request.url.as_str()),
+                incoming.status.to_u16(),
+                millis(incoming.elapsed)
+            );
+
+            for interceptor in &self.interceptors {
+                interceptor.on_response(&incoming);
+            }
+        }
+
+        if let Some(usage) = response
+            .headers()
+            .get_raw(LIMIT_INFO_HEADER)
+            .and_then(|raw| raw.one())
+            .and_then(|value| str::from_utf8(value).ok())
+            .and_then(ApiUsage::parse)
+        {
+            debug!("{}", usage);
+            self.usage.set(Some(usage));
+        }
+
+        Ok(response)
+    }
+}
+
+/// Strips the query string from a url so that SOQL and other parameters never reach the logs
+pub fn redact_url(url: &str) -> String {
+    match url.find('?') {
+        Some(index) => url[..index].to_owned() + "?<redacted>",
+        None => url.to_owned(),
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::{mock, Mock};
+    use reqwest::{Method, StatusCode};
+    use reqwest::header::{Authorization, Bearer, Headers};
+
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use http::{HttpClient, IncomingResponse, Interceptor, OutgoingRequest, redact_url};
+    use rate_limit::RateLimiter;
+
+    struct CallOptions;
+
+    impl Interceptor for CallOptions {
+        fn on_request(&self, request: &mut OutgoingRequest) {
+            request.headers.set_raw(
+                "Sforce-Call-Options",
+                "client=ourapp",
+            );
+        }
+    }
+
+    struct Recorder {
+        statuses: Arc<Mutex<Vec<u16>>>,
+    }
+
+    impl Interceptor for Recorder {
+        fn on_response(&self, response: &IncomingResponse) {
+            self.statuses.lock().unwrap().push(response.status.to_u16());
+        }
+    }
+
+    struct ThrottleRecorder {
+        waits: Arc<Mutex<Vec<Duration>>>,
+    }
+
+    impl Interceptor for ThrottleRecorder {
+        fn on_throttle(&self, _request: &OutgoingRequest, waited: Duration) {
+            self.waits.lock().unwrap().push(waited);
+        }
+    }
+
+    fn header_mock(path: &str, code: usize) -> Mock {
+        let mut m = mock("GET", path);
+        m.with_status(code).with_body("{}").match_header(
+            "Sforce-Call-Options",
+            "client=ourapp",
+        );
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_interceptors_see_request_and_response() {
+        let statuses = Arc::new(Mutex::new(vec![]));
+        let mut client = HttpClient::new().unwrap();
+        client.add_interceptor(CallOptions);
+        client.add_interceptor(Recorder { statuses: statuses.clone() });
+
+        let mock = header_mock("/intercepted", 201);
+        let url = mockito::SERVER_URL.to_owned() + "/intercepted";
+        let response = client
+            .send(Method::Get, url.as_str(), Headers::new(), |request| request)
+            .unwrap();
+
+        mock.remove();
+
+        assert_eq!(201, response.status().to_u16());
+        assert_eq!(vec![201], *statuses.lock().unwrap());
+    }
+
+    fn token_headers() -> Headers {
+        let mut headers = Headers::new();
+        headers.set(Authorization(Bearer { token: "bearer-token".to_owned() }));
+        headers.set_raw("X-SFDC-Session", "session-token");
+        headers.set_raw("Sforce-Call-Options", "client=ourapp");
+        headers
+    }
+
+    fn token_request() -> OutgoingRequest {
+        OutgoingRequest {
+            method: Method::Get,
+            url: "https://na1.salesforce.com/services/data/".to_owned(),
+            headers: token_headers(),
+        }
+    }
+
+    #[test]
+    fn test_request_debug_output_hides_tokens() {
+        let output = format!("{:?}", token_request());
+
+        assert!(output.contains("client=ourapp"));
+        assert!(!output.contains("bearer-token"));
+        assert!(!output.contains("session-token"));
+    }
+
+    #[test]
+    fn test_response_debug_output_hides_tokens() {
+        let request = token_request();
+        let headers = token_headers();
+        let response = IncomingResponse {
+            request: &request,
+            status: StatusCode::Ok,
+            headers: &headers,
+            elapsed: Duration::from_millis(5),
+        };
+
+        let output = format!("{:?}", response);
+
+        assert!(output.contains("client=ourapp"));
+        assert!(!output.contains("bearer-token"));
+        assert!(!output.contains("session-token"));
+    }
+
+    #[test]
+    fn test_redacts_query_strings() {
+        assert_eq!(
+            "https://na1.salesforce.com/services/data/v20.0/query?<redacted>",
+            redact_url("https://na1.salesforce.com/services/data/v20.0/query?q=SELECT Id FROM User")
+        );
+        assert_eq!(
+            "https://na1.salesforce.com/services/data/",
+            redact_url("https://na1.salesforce.com/services/data/")
+        );
+    }
+
+    #[test]
+    fn test_reports_throttled_requests() {
+        let waits = Arc::new(Mutex::new(vec![]));
+        let mut client = HttpClient::new().unwrap();
+        client.set_rate_limiter(Some(Arc::new(RateLimiter::new(20.0).with_burst(1))));
+        client.add_interceptor(ThrottleRecorder { waits: waits.clone() });
+
+        let mut m = mock("GET", "/throttled");
+        m.with_status(200).with_body("{}");
+        m.create();
+
+        let url = mockito::SERVER_URL.to_owned() + "/throttled";
+        client
+            .send(Method::Get, url.as_str(), Headers::new(), |request| request)
+            .unwrap();
+        client
+            .send(Method::Get, url.as_str(), Headers::new(), |request| request)
+            .unwrap();
+
+        m.remove();
+
+        assert_eq!(1, waits.lock().unwrap().len());
+    }
+}
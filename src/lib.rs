@@ -1,3 +1,7 @@
+extern crate chrono;
+extern crate csv;
+#[macro_use]
+extern crate log;
 #[cfg(test)]
 extern crate mockito;
 extern crate reqwest;
@@ -6,28 +10,93 @@ extern crate serde;
 extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
+extern crate url;
 
+mod blob;
+mod bulk1;
+mod bulk2;
+mod cdc;
+mod collections;
+mod composite;
+mod describe;
+mod describe_cache;
+mod events;
+mod http;
+mod limits;
 mod query;
+mod rate_limit;
+mod replay;
+mod replication;
+mod rest;
+mod search;
+mod secret;
+mod sobject;
+mod streaming;
 mod token;
+mod tree;
+mod usage;
+mod versions;
 
 use std::error::Error;
 use std::fmt;
+use std::io::{BufReader, Read, Write};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use reqwest::{Client, Error as ClientError};
+use chrono::{DateTime, Utc};
+use reqwest::Body;
+use reqwest::Error as ClientError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 
+use http::HttpClient;
 use query::{QueryError, QueryRequest, QueryResponse};
+use rest::{RestRequest, RestResult};
+use secret::Secret;
 use token::{TokenError, TokenRequest, TokenResponse};
 
+pub use bulk1::{BulkBatchInfo, BulkBatchState, BulkJobInfo, BulkJobState, ConcurrencyMode,
+                ContentType, JobSpec, PkChunking};
+pub use bulk2::{IngestJobRequest, IngestResults, JobInfo, JobState, Operation, QueryJobRequest,
+                QueryRows, ResultsPage};
+pub use cdc::{ALL_CHANGE_EVENTS, ChangeEvent, ChangeEventHeader, ChangeType, change_channel};
+pub use composite::{BatchRequest, BatchResponse, BatchResult, BatchSubrequest, CompositeRequest,
+                    CompositeResponse, Graph, GraphRequest, GraphResponse, GraphResult, Subrequest,
+                    Subresponse};
+pub use describe::{ChildRelationship, DescribeGlobal, Field, GlobalSObject, PicklistValue,
+                   RecordTypeInfo, SObjectDescribe};
+pub use describe_cache::DescribeCache;
+pub use events::PublishResult;
+pub use http::{IncomingResponse, Interceptor, OutgoingRequest};
+pub use limits::{Limit, Limits};
+pub use rate_limit::RateLimiter;
+pub use replay::{FileReplayStore, MemoryReplayStore, ReplayStore};
+pub use replication::{DeletedRecord, DeletedRecords, UpdatedRecords};
+pub use rest::{ApiFailure, RestError, RestFailure};
+pub use search::{ParameterizedSearch, SearchResponse, SearchSObject};
+pub use sobject::{SaveResult, UpsertResult};
+pub use streaming::{Advice, Message, REPLAY_ALL, REPLAY_NEW, Session, StreamingEvent,
+                    Subscription};
+pub use tree::{PlanEntry, TreeError, TreeFile, TreeResponse, TreeResult, resolve_references};
+pub use usage::ApiUsage;
+pub use versions::{ApiVersion, VersionPolicy};
+
 #[derive(Debug)]
 pub struct SFClient {
     login_url: String,
     version: String,
     client_id: String,
-    client_secret: String,
+    client_secret: Secret,
     username: String,
-    password: String,
-    client: Client,
+    password: Secret,
+    client: HttpClient,
     attempt_limit: u8,
+    usage_threshold: Option<u64>,
+    version_policy: VersionPolicy,
+    version_negotiated: bool,
+    describe_cache: Option<DescribeCache>,
     token: Option<TokenResponse>,
 }
 
@@ -53,17 +122,21 @@ impl SFClient {
             return Err(SFClientError::InvalidVersion);
         }
 
-        Client::new()
+        HttpClient::new()
             .map(|client| {
                 SFClient {
                     login_url: url,
                     version: api_version,
                     client_id: client_id.into(),
-                    client_secret: client_secret.into(),
+                    client_secret: Secret::new(client_secret),
                     username: username.into(),
-                    password: password.into(),
+                    password: Secret::new(password),
                     client: client,
                     attempt_limit: 3,
+                    usage_threshold: None,
+                    version_policy: VersionPolicy::default(),
+                    version_negotiated: false,
+                    describe_cache: None,
                     token: None,
                 }
             })
@@ -74,6 +147,43 @@ impl SFClient {
         self.attempt_limit = attempt_limit;
     }
 
+    /// Holds every request, including authentication, to the limiter's rate and in flight
+    /// limits. Waits are reported through `Interceptor::on_throttle`. A request leaves the in
+    /// flight count once its response headers arrive, before any streamed body is read.
+    pub fn set_rate_limiter(&mut self, limiter: Option<Arc<RateLimiter>>) {
+        self.client.set_rate_limiter(limiter);
+    }
+
+    pub fn add_interceptor<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.client.add_interceptor(interceptor);
+    }
+
+    /// Refuses to send further API calls once the org has used `threshold` requests of its daily
+    /// allowance, as last reported by the `Sforce-Limit-Info` header
+    pub fn set_usage_threshold(&mut self, threshold: Option<u64>) {
+        self.usage_threshold = threshold;
+    }
+
+    pub fn api_usage(&self) -> Option<ApiUsage> {
+        self.client.usage()
+    }
+
+    /// Controls how the configured version is checked against, or replaced by, the versions the
+    /// instance supports. Negotiation happens once, after the first authentication.
+    pub fn set_version_policy(&mut self, policy: VersionPolicy) {
+        self.version_policy = policy;
+        self.version_negotiated = false;
+    }
+
+    /// Caches describe results, revalidating them with `If-Modified-Since` on later calls
+    pub fn set_describe_cache(&mut self, cache: Option<DescribeCache>) {
+        self.describe_cache = cache;
+    }
+
+    pub fn version(&self) -> &str {
+        self.version.as_str()
+    }
+
     pub fn set_token(&mut self, token: TokenResponse) {
         self.token = Some(token);
     }
@@ -86,29 +196,90 @@ impl SFClient {
     }
 
     fn authenticate(&mut self) -> SFClientResult<()> {
+        info!("Requesting access token from {}", self.login_url);
+
         let request = TokenRequest::new(
             self.login_url.as_str(),
             self.client_id.as_str(),
-            self.client_secret.as_str(),
+            self.client_secret.expose(),
             self.username.as_str(),
-            self.password.as_str(),
+            self.password.expose(),
             &self.client,
         );
 
         let token_resp = request.send();
-        let token = token_resp.map_err(SFClientError::Token)?;
+        let token = token_resp.map_err(|err| {
+            warn!("Failed to authenticate: {}", err);
+            SFClientError::Token(err)
+        })?;
+        debug!("Authenticated against instance {}", token.url());
         self.token = Some(token);
 
         Ok(())
     }
 
+    fn check_usage(&self) -> SFClientResult<()> {
+        match (self.usage_threshold, self.client.usage()) {
+            (Some(threshold), Some(usage)) if usage.used >= threshold => {
+                warn!("Refusing API call, {} (threshold {})", usage, threshold);
+                Err(SFClientError::UsageThresholdReached(usage))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn negotiate_version(&mut self) -> SFClientResult<()> {
+        let available = match self.token {
+            Some(ref token) => {
+                versions::get_versions(&RestRequest::new(
+                    token.url(),
+                    self.version.as_str(),
+                    token.access(),
+                    &self.client,
+                ))?
+            }
+            None => return Err(SFClientError::TokenUnavailable),
+        };
+
+        let selected = versions::select_version(
+            &available,
+            self.version_policy,
+            self.version.as_str(),
+        ).map(|version| version.path_version())
+            .ok_or_else(|| SFClientError::UnsupportedVersion(self.version.clone()))?;
+
+        if selected != self.version {
+            info!("Switching API version from {} to {}", self.version, selected);
+        }
+
+        self.version = selected;
+        self.version_negotiated = true;
+
+        Ok(())
+    }
+
+    /// Gets the client ready to send an API call by checking usage, authenticating and settling
+    /// on an API version as needed
+    fn prepare(&mut self) -> SFClientResult<()> {
+        self.check_usage()?;
+
+        if self.token.is_none() {
+            debug!("No access token available, authenticating");
+            self.authenticate()?;
+        };
+
+        if self.version_policy != VersionPolicy::Pinned && !self.version_negotiated {
+            self.negotiate_version()?;
+        }
+
+        Ok(())
+    }
+
     fn build_request<'a, 'b>(
         &'a mut self,
         query: &'b str,
     ) -> SFClientResult<QueryRequest<'a, 'a, 'b, 'a, 'a>> {
-        if self.token.is_none() {
-            self.authenticate()?;
-        };
+        self.prepare()?;
 
         if let Some(ref token) = self.token {
             Ok(QueryRequest::new(
@@ -133,16 +304,24 @@ impl SFClient {
     }
 
     fn attempt_query(&mut self, query: &str, attempt: u8) -> SFClientResult<QueryResponse> {
+        debug!(
+            "Query attempt {} of {}",
+            u32::from(attempt) + 1,
+            u32::from(self.attempt_limit) + 1
+        );
+
         self.do_query(query).or_else(
             |err| if attempt < self.attempt_limit {
-                if let SFClientError::Query(QueryError::API(failure)) = err {
-                    if failure.error_code == 401 {
-                        self.token = None;
-                    }
+                warn!("Query attempt {} failed: {}", u32::from(attempt) + 1, err);
+
+                if token_rejected(&err) {
+                    info!("Access token was rejected, re-authenticating");
+                    self.token = None;
                 }
 
                 self.attempt_query(query, attempt + 1)
             } else {
+                warn!("Query failed after {} attempts: {}", u32::from(attempt) + 1, err);
                 Err(err)
             },
         )
@@ -151,18 +330,838 @@ impl SFClient {
     pub fn query(&mut self, query: &str) -> SFClientResult<QueryResponse> {
         self.attempt_query(query, 0)
     }
+
+    fn rest_request(&mut self) -> SFClientResult<RestRequest> {
+        self.prepare()?;
+
+        if let Some(ref token) = self.token {
+            Ok(RestRequest::new(
+                token.url(),
+                self.version.as_str(),
+                token.access(),
+                &self.client,
+            ))
+        } else {
+            Err(SFClientError::TokenUnavailable)
+        }
+    }
+
+    fn do_rest<T, F>(&mut self, call: &mut F) -> SFClientResult<T>
+    where
+        F: FnMut(&RestRequest) -> RestResult<T>,
+    {
+        self.rest_request().and_then(
+            |request| call(&request).map_err(SFClientError::from),
+        )
+    }
+
+    fn attempt_rest<T, F>(&mut self, call: &mut F, attempt: u8) -> SFClientResult<T>
+    where
+        F: FnMut(&RestRequest) -> RestResult<T>,
+    {
+        debug!(
+            "API attempt {} of {}",
+            u32::from(attempt) + 1,
+            u32::from(self.attempt_limit) + 1
+        );
+
+        self.do_rest(call).or_else(
+            |err| if attempt < self.attempt_limit {
+                warn!("API attempt {} failed: {}", u32::from(attempt) + 1, err);
+
+                if token_rejected(&err) {
+                    info!("Access token was rejected, re-authenticating");
+                    self.token = None;
+                }
+
+                self.attempt_rest(call, attempt + 1)
+            } else {
+                warn!("API call failed after {} attempts: {}", u32::from(attempt) + 1, err);
+                Err(err)
+            },
+        )
+    }
+
+    /// Runs `call` against an authenticated request, retrying and re-authenticating the same way
+    /// that queries do
+    fn rest<T, F>(&mut self, mut call: F) -> SFClientResult<T>
+    where
+        F: FnMut(&RestRequest) -> RestResult<T>,
+    {
+        self.attempt_rest(&mut call, 0)
+    }
+
+    fn attempt_at_most_once<T, F>(&mut self, call: &mut F, attempt: u8) -> SFClientResult<T>
+    where
+        F: FnMut(&RestRequest) -> RestResult<T>,
+    {
+        let (sent, result) = match self.rest_request() {
+            Ok(request) => (true, call(&request).map_err(SFClientError::from)),
+            Err(err) => (false, Err(err)),
+        };
+
+        match result {
+            Err(err) => {
+                let rejected = token_rejected(&err);
+
+                if rejected {
+                    info!("Access token was rejected, re-authenticating");
+                    self.token = None;
+                }
+
+                if attempt < self.attempt_limit && (rejected || !sent) {
+                    warn!("API attempt {} failed: {}", u32::from(attempt) + 1, err);
+                    self.attempt_at_most_once(call, attempt + 1)
+                } else {
+                    Err(err)
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Runs a call that must not take effect twice, such as a create or a download into a writer.
+    /// It is only repeated when the request was never sent or the access token was rejected, as
+    /// any other failure may have happened after the server acted on it.
+    fn rest_at_most_once<T, F>(&mut self, mut call: F) -> SFClientResult<T>
+    where
+        F: FnMut(&RestRequest) -> RestResult<T>,
+    {
+        self.attempt_at_most_once(&mut call, 0)
+    }
+
+    /// Runs `call` a single time, for calls that consume a reader and so cannot be repeated. A
+    /// rejected access token is still dropped so that the next call authenticates again.
+    fn rest_once<T, F>(&mut self, call: F) -> SFClientResult<T>
+    where
+        F: FnOnce(&RestRequest) -> RestResult<T>,
+    {
+        let result = self.rest_request().and_then(
+            |request| call(&request).map_err(SFClientError::from),
+        );
+
+        if let Err(ref err) = result {
+            if token_rejected(err) {
+                info!("Access token was rejected, re-authenticating on the next call");
+                self.token = None;
+            }
+        }
+
+        result
+    }
+
+    /// Runs a SOSL search, with the matching records grouped by type through `grouped`
+    pub fn search(&mut self, sosl: &str) -> SFClientResult<SearchResponse> {
+        self.rest(|request| search::search(request, sosl))
+    }
+
+    pub fn parameterized_search(
+        &mut self,
+        parameters: &ParameterizedSearch,
+    ) -> SFClientResult<SearchResponse> {
+        self.rest(|request| search::parameterized_search(request, parameters))
+    }
+
+    pub fn limits(&mut self) -> SFClientResult<Limits> {
+        self.rest(limits::get_limits)
+    }
+
+    /// Creates a record, the call is not repeated after failures that may have saved it
+    pub fn create<T: Serialize>(&mut self, sobject: &str, record: &T) -> SFClientResult<SaveResult> {
+        self.rest_at_most_once(|request| sobject::create(request, sobject, record))
+    }
+
+    /// Fetches a record by Id, returning every field when `fields` is empty
+    pub fn retrieve<T: DeserializeOwned>(
+        &mut self,
+        sobject: &str,
+        id: &str,
+        fields: &[&str],
+    ) -> SFClientResult<T> {
+        self.rest(|request| sobject::retrieve(request, sobject, id, fields))
+    }
+
+    pub fn update<T: Serialize>(&mut self, sobject: &str, id: &str, record: &T) -> SFClientResult<()> {
+        self.rest(|request| sobject::update(request, sobject, id, record))
+    }
+
+    /// Creates or updates the record whose `field` external id matches `value`. Several matching
+    /// records are reported as `RestError::DuplicateExternalId`.
+    pub fn upsert<T: Serialize>(
+        &mut self,
+        sobject: &str,
+        field: &str,
+        value: &str,
+        record: &T,
+    ) -> SFClientResult<UpsertResult> {
+        self.rest(|request| sobject::upsert(request, sobject, field, value, record))
+    }
+
+    /// Deletes a record, the call is not repeated after failures that may have deleted it, as the
+    /// repeat would report the missing record as a failure
+    pub fn delete(&mut self, sobject: &str, id: &str) -> SFClientResult<()> {
+        self.rest_at_most_once(|request| sobject::delete(request, sobject, id))
+    }
+
+    /// Sends `items` a chunk at a time, keeping the results of the chunks already committed when
+    /// a later one fails
+    fn chunked<C, R, F>(&mut self, items: &[C], mut send: F) -> CollectionResult<R>
+    where
+        F: FnMut(&mut SFClient, &[C]) -> SFClientResult<Vec<R>>,
+    {
+        let mut completed = Vec::with_capacity(items.len());
+
+        for chunk in items.chunks(collections::COLLECTION_LIMIT) {
+            match send(self, chunk) {
+                Ok(results) => completed.extend(results),
+                Err(error) => {
+                    return Err(CollectionError {
+                        completed: completed,
+                        error: error,
+                    })
+                }
+            };
+        }
+
+        Ok(completed)
+    }
+
+    /// Creates any number of records through sObject Collections, 200 per call. `all_or_none`
+    /// applies to each call separately, and results are returned in the order of `records`.
+    /// Chunks are not repeated after failures that may have saved them.
+    pub fn create_collection<T: Serialize>(
+        &mut self,
+        sobject: &str,
+        records: &[T],
+        all_or_none: bool,
+    ) -> CollectionResult<SaveResult> {
+        self.chunked(records, |client, chunk| {
+            client.rest_at_most_once(|request| {
+                collections::create(request, sobject, chunk, all_or_none)
+            })
+        })
+    }
+
+    pub fn update_collection<T: Serialize>(
+        &mut self,
+        sobject: &str,
+        records: &[T],
+        all_or_none: bool,
+    ) -> CollectionResult<SaveResult> {
+        self.chunked(records, |client, chunk| {
+            client.rest(|request| {
+                collections::update(request, sobject, chunk, all_or_none)
+            })
+        })
+    }
+
+    pub fn upsert_collection<T: Serialize>(
+        &mut self,
+        sobject: &str,
+        field: &str,
+        records: &[T],
+        all_or_none: bool,
+    ) -> CollectionResult<SaveResult> {
+        self.chunked(records, |client, chunk| {
+            client.rest(|request| {
+                collections::upsert(request, sobject, field, chunk, all_or_none)
+            })
+        })
+    }
+
+    pub fn delete_collection(
+        &mut self,
+        ids: &[&str],
+        all_or_none: bool,
+    ) -> CollectionResult<SaveResult> {
+        self.chunked(ids, |client, chunk| {
+            client.rest(|request| collections::delete(request, chunk, all_or_none))
+        })
+    }
+
+    /// Sends up to 25 subrequests, which may refer to each other's results, in a single call. The
+    /// call is not repeated after failures that may have applied its writes.
+    pub fn composite(&mut self, composite: &CompositeRequest) -> SFClientResult<CompositeResponse> {
+        self.rest_at_most_once(|request| composite::send_composite(request, composite))
+    }
+
+    /// Sends several graphs of dependent subrequests in one call, each graph succeeding or failing
+    /// as a unit. The call is not repeated after failures that may have committed the graphs.
+    pub fn composite_graph(&mut self, graphs: &GraphRequest) -> SFClientResult<GraphResponse> {
+        self.rest_at_most_once(|request| composite::send_graphs(request, graphs))
+    }
+
+    /// Creates nested records rooted at `sobject`, each tagged with an `attributes.referenceId`
+    pub fn import_tree(&mut self, sobject: &str, records: &[Value]) -> SFClientResult<TreeResponse> {
+        self.rest_at_most_once(|request| tree::create_tree(request, sobject, records))
+    }
+
+    /// Sends up to 25 unrelated subrequests in one call, stopping at the first failure when
+    /// `halt_on_error` was requested. The call is not repeated after failures that may have
+    /// applied its writes.
+    pub fn batch(&mut self, batch: &BatchRequest) -> SFClientResult<BatchResponse> {
+        self.rest_at_most_once(|request| composite::send_batch(request, batch))
+    }
+
+    /// Creates an ingest job, the call is not repeated after failures that may have created it
+    pub fn create_ingest_job(&mut self, job: &IngestJobRequest) -> SFClientResult<JobInfo> {
+        self.rest_at_most_once(|request| bulk2::create_ingest_job(request, job))
+    }
+
+    /// Uploads the CSV data for an open ingest job, which accepts at most 100MB in one upload
+    pub fn upload_ingest_data(&mut self, job_id: &str, data: &[u8]) -> SFClientResult<()> {
+        self.rest_at_most_once(|request| {
+            bulk2::upload_job_data(request, job_id, data.to_vec())
+        })
+    }
+
+    /// Streams CSV data for an open ingest job from `reader`, the upload is not retried
+    pub fn upload_ingest_reader<R: Read + Send + 'static>(
+        &mut self,
+        job_id: &str,
+        reader: R,
+    ) -> SFClientResult<()> {
+        self.rest_once(|request| {
+            bulk2::upload_job_data(request, job_id, Body::new(reader))
+        })
+    }
+
+    pub fn close_ingest_job(&mut self, job_id: &str) -> SFClientResult<JobInfo> {
+        self.rest(|request| bulk2::close_ingest_job(request, job_id))
+    }
+
+    pub fn abort_ingest_job(&mut self, job_id: &str) -> SFClientResult<JobInfo> {
+        self.rest(|request| bulk2::abort_ingest_job(request, job_id))
+    }
+
+    pub fn ingest_job(&mut self, job_id: &str) -> SFClientResult<JobInfo> {
+        self.rest(|request| bulk2::ingest_job(request, job_id))
+    }
+
+    pub fn delete_ingest_job(&mut self, job_id: &str) -> SFClientResult<()> {
+        self.rest(|request| bulk2::delete_ingest_job(request, job_id))
+    }
+
+    /// Polls an ingest job every `interval` until it finishes, reporting jobs that failed or were
+    /// aborted as `SFClientError::JobFailed` and jobs still running after `timeout` as
+    /// `SFClientError::Timeout`
+    pub fn wait_for_ingest_job(
+        &mut self,
+        job_id: &str,
+        interval: Duration,
+        timeout: Duration,
+    ) -> SFClientResult<JobInfo> {
+        let started = Instant::now();
+
+        loop {
+            let job = self.ingest_job(job_id)?;
+
+            match job.state {
+                JobState::JobComplete => return Ok(job),
+                JobState::Failed | JobState::Aborted => return Err(SFClientError::JobFailed(job)),
+                _ => {
+                    debug!("Ingest job {} is {:?}, checking again shortly", job_id, job.state);
+                    wait_to_poll(job_id, started, interval, timeout)?;
+                }
+            };
+        }
+    }
+
+    /// Writes the CSV results of a finished ingest job into `writer`, returning the bytes written
+    pub fn ingest_results<W: Write>(
+        &mut self,
+        job_id: &str,
+        results: IngestResults,
+        writer: &mut W,
+    ) -> SFClientResult<u64> {
+        self.rest_at_most_once(|request| {
+            bulk2::ingest_results(request, job_id, results, &mut *writer)
+        })
+    }
+
+    /// Loads CSV data of any size, splitting it between as many ingest jobs as the upload limit
+    /// requires. Each job is closed once its data is uploaded and the jobs are returned in order.
+    pub fn ingest_csv<R: Read>(&mut self, job: &IngestJobRequest, data: R) -> SFClientResult<Vec<JobInfo>> {
+        let mut chunker = bulk2::CsvChunker::new(BufReader::new(data), bulk2::UPLOAD_LIMIT)
+            .map_err(RestError::Io)?;
+        let mut jobs = vec![];
+
+        while let Some(chunk) = chunker.next_chunk().map_err(RestError::Io)? {
+            let created = self.create_ingest_job(job)?;
+            self.upload_ingest_data(created.id.as_str(), chunk.as_slice())?;
+            jobs.push(self.close_ingest_job(created.id.as_str())?);
+        }
+
+        Ok(jobs)
+    }
+
+    /// Creates a query job, the call is not repeated after failures that may have created it
+    pub fn create_query_job(&mut self, job: &QueryJobRequest) -> SFClientResult<JobInfo> {
+        self.rest_at_most_once(|request| bulk2::create_query_job(request, job))
+    }
+
+    pub fn query_job(&mut self, job_id: &str) -> SFClientResult<JobInfo> {
+        self.rest(|request| bulk2::query_job(request, job_id))
+    }
+
+    pub fn abort_query_job(&mut self, job_id: &str) -> SFClientResult<JobInfo> {
+        self.rest(|request| bulk2::abort_query_job(request, job_id))
+    }
+
+    pub fn delete_query_job(&mut self, job_id: &str) -> SFClientResult<()> {
+        self.rest(|request| bulk2::delete_query_job(request, job_id))
+    }
+
+    /// Polls a query job every `interval` until it finishes, reporting jobs that failed or were
+    /// aborted as `SFClientError::JobFailed` and jobs still running after `timeout` as
+    /// `SFClientError::Timeout`
+    pub fn wait_for_query_job(
+        &mut self,
+        job_id: &str,
+        interval: Duration,
+        timeout: Duration,
+    ) -> SFClientResult<JobInfo> {
+        let started = Instant::now();
+
+        loop {
+            let job = self.query_job(job_id)?;
+
+            match job.state {
+                JobState::JobComplete => return Ok(job),
+                JobState::Failed | JobState::Aborted => return Err(SFClientError::JobFailed(job)),
+                _ => {
+                    debug!("Query job {} is {:?}, checking again shortly", job_id, job.state);
+                    wait_to_poll(job_id, started, interval, timeout)?;
+                }
+            };
+        }
+    }
+
+    /// Writes every page of a completed query job's results into `writer` as a single CSV
+    /// document, returning the number of records written
+    pub fn query_job_results<W: Write>(&mut self, job_id: &str, writer: &mut W) -> SFClientResult<u64> {
+        let mut locator: Option<String> = None;
+        let mut records = 0;
+
+        loop {
+            let page = self.rest_at_most_once(|request| {
+                bulk2::query_results(
+                    request,
+                    job_id,
+                    locator.as_ref().map(|locator| locator.as_str()),
+                    locator.is_some(),
+                    &mut *writer,
+                )
+            })?;
+
+            records += page.records.unwrap_or(0);
+
+            match page.locator {
+                Some(next) => locator = Some(next),
+                None => return Ok(records),
+            };
+        }
+    }
+
+    /// Deserializes the rows of a completed query job, downloading a page at a time
+    pub fn query_job_rows<T: DeserializeOwned>(&mut self, job_id: &str) -> QueryRows<T> {
+        QueryRows::new(self, job_id)
+    }
+
+    /// Runs `query` as a Bulk 2.0 query job and writes all of its results into `writer`, waiting
+    /// at most `timeout` for the job to finish
+    pub fn export_query<W: Write>(
+        &mut self,
+        query: &str,
+        interval: Duration,
+        timeout: Duration,
+        writer: &mut W,
+    ) -> SFClientResult<u64> {
+        let job = self.create_query_job(&QueryJobRequest::new(query))?;
+        self.wait_for_query_job(job.id.as_str(), interval, timeout)?;
+        self.query_job_results(job.id.as_str(), writer)
+    }
+
+    /// Creates a Bulk API 1.0 job, which PK chunking splits into batches by Id range. The call is
+    /// not repeated after failures that may have created the job.
+    pub fn create_bulk_job(
+        &mut self,
+        spec: &JobSpec,
+        pk_chunking: Option<&PkChunking>,
+    ) -> SFClientResult<BulkJobInfo> {
+        self.rest_at_most_once(|request| bulk1::create_job(request, spec, pk_chunking))
+    }
+
+    pub fn bulk_job(&mut self, job_id: &str) -> SFClientResult<BulkJobInfo> {
+        self.rest(|request| bulk1::job(request, job_id))
+    }
+
+    pub fn close_bulk_job(&mut self, job_id: &str) -> SFClientResult<BulkJobInfo> {
+        self.rest(|request| bulk1::close_job(request, job_id))
+    }
+
+    pub fn abort_bulk_job(&mut self, job_id: &str) -> SFClientResult<BulkJobInfo> {
+        self.rest(|request| bulk1::abort_job(request, job_id))
+    }
+
+    /// Adds a batch to a job, the call is not repeated after failures that may have added it as
+    /// its records would be loaded twice
+    pub fn add_bulk_batch(
+        &mut self,
+        job_id: &str,
+        content_type: ContentType,
+        data: &[u8],
+    ) -> SFClientResult<BulkBatchInfo> {
+        self.rest_at_most_once(|request| {
+            bulk1::add_batch(request, job_id, content_type, data.to_vec())
+        })
+    }
+
+    pub fn bulk_batch(&mut self, job_id: &str, batch_id: &str) -> SFClientResult<BulkBatchInfo> {
+        self.rest(|request| bulk1::batch(request, job_id, batch_id))
+    }
+
+    pub fn bulk_batches(&mut self, job_id: &str) -> SFClientResult<Vec<BulkBatchInfo>> {
+        self.rest(|request| bulk1::batches(request, job_id))
+    }
+
+    /// Polls the batches of a job every `interval` until none of them are queued or in progress,
+    /// giving up with `SFClientError::Timeout` once `timeout` has passed
+    pub fn wait_for_bulk_batches(
+        &mut self,
+        job_id: &str,
+        interval: Duration,
+        timeout: Duration,
+    ) -> SFClientResult<Vec<BulkBatchInfo>> {
+        let started = Instant::now();
+
+        loop {
+            let batches = self.bulk_batches(job_id)?;
+
+            if batches.iter().all(|batch| batch.state.is_finished()) {
+                return Ok(batches);
+            }
+
+            debug!("Bulk job {} still has batches in progress", job_id);
+            wait_to_poll(job_id, started, interval, timeout)?;
+        }
+    }
+
+    /// Writes the per record results of an ingest batch into `writer`
+    pub fn bulk_batch_results<W: Write>(
+        &mut self,
+        job_id: &str,
+        batch_id: &str,
+        writer: &mut W,
+    ) -> SFClientResult<u64> {
+        self.rest_at_most_once(|request| {
+            bulk1::batch_results(request, job_id, batch_id, &mut *writer)
+        })
+    }
+
+    /// Writes every result set of a completed query batch into `writer`, returning the bytes
+    /// written
+    pub fn bulk_query_results<W: Write>(
+        &mut self,
+        job_id: &str,
+        batch_id: &str,
+        writer: &mut W,
+    ) -> SFClientResult<u64> {
+        let result_ids = self.rest(|request| bulk1::query_result_ids(request, job_id, batch_id))?;
+        let mut written = 0;
+
+        for result_id in result_ids {
+            written += self.rest_at_most_once(|request| {
+                bulk1::query_result(request, job_id, batch_id, result_id.as_str(), &mut *writer)
+            })?;
+        }
+
+        Ok(written)
+    }
+
+    /// Runs `query` as a PK chunked Bulk API 1.0 query job and writes the results of every chunk
+    /// into `writer`, one CSV document after another. The chunks may take up to `timeout` to run.
+    pub fn pk_chunked_query<W: Write>(
+        &mut self,
+        sobject: &str,
+        query: &str,
+        pk_chunking: &PkChunking,
+        interval: Duration,
+        timeout: Duration,
+        writer: &mut W,
+    ) -> SFClientResult<u64> {
+        let spec = JobSpec::new(Operation::Query, sobject, ContentType::Csv);
+        let job = self.create_bulk_job(&spec, Some(pk_chunking))?;
+        self.add_bulk_batch(job.id.as_str(), ContentType::Csv, query.as_bytes())?;
+
+        let batches = self.wait_for_bulk_batches(job.id.as_str(), interval, timeout)?;
+        self.close_bulk_job(job.id.as_str())?;
+
+        let mut written = 0;
+
+        for batch in batches {
+            match batch.state {
+                BulkBatchState::Completed => {
+                    written += self.bulk_query_results(job.id.as_str(), batch.id.as_str(), writer)?;
+                }
+                BulkBatchState::Failed => return Err(SFClientError::BatchFailed(batch)),
+                _ => (),
+            };
+        }
+
+        Ok(written)
+    }
+
+    /// Publishes a platform event such as `Order_Shipped__e`. The call is not repeated after
+    /// failures that may have published the event, as subscribers would receive it twice.
+    pub fn publish_event<T: Serialize>(
+        &mut self,
+        event_type: &str,
+        event: &T,
+    ) -> SFClientResult<PublishResult> {
+        self.rest_at_most_once(|request| events::publish(request, event_type, event))
+    }
+
+    /// Publishes any number of platform events through sObject Collections, 200 per call, with
+    /// results returned in the order of `events`. A failed call keeps the results of the calls
+    /// before it, which were already published.
+    pub fn publish_events<T: Serialize>(
+        &mut self,
+        event_type: &str,
+        events: &[T],
+    ) -> CollectionResult<PublishResult> {
+        self.chunked(events, |client, chunk| {
+            client.rest_at_most_once(|request| {
+                events::publish_batch(request, event_type, chunk)
+            })
+        })
+    }
+
+    /// Subscribes to a PushTopic, platform event or generic streaming channel, replaying events
+    /// after `replay_id`. Add further channels to the returned subscription before iterating it.
+    pub fn subscribe(&mut self, channel: &str, replay_id: i64) -> Subscription {
+        let mut subscription = Subscription::new(self);
+        subscription.add_channel(channel, replay_id);
+        subscription
+    }
+
+    /// Subscribes to `channels`, such as change data capture channels, resuming each from the
+    /// replay id saved in `store` or from new events when it has none
+    pub fn subscribe_with_store(&mut self, channels: &[&str], store: Box<ReplayStore>) -> Subscription {
+        let mut subscription = Subscription::new(self);
+        subscription.set_replay_store(store);
+
+        for channel in channels {
+            subscription.add_channel(channel, REPLAY_NEW);
+        }
+
+        subscription
+    }
+
+    /// Lists the Ids of `sobject` records changed between `start` and `end`. The window must start
+    /// within the last 30 days, the response's `latest_date_covered` is where the next one starts.
+    /// Windows holding more than the API's 600,000 Ids are split in two and fetched separately.
+    /// The call is made once per window, as its window errors would only repeat.
+    pub fn updated_records(
+        &mut self,
+        sobject: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> SFClientResult<UpdatedRecords> {
+        replication::check_window(start, end, &Utc::now()).map_err(
+            SFClientError::InvalidWindow,
+        )?;
+        self.replication_window(
+            sobject,
+            start,
+            end,
+            &replication::updated,
+            UpdatedRecords::merge,
+        )
+    }
+
+    /// Lists the `sobject` records deleted between `start` and `end`, under the same window rules
+    /// as `updated_records`. A window starting before the earliest date the instance still holds
+    /// deletions for fails with `SFClientError::ReplicationDateUnavailable`.
+    pub fn deleted_records(
+        &mut self,
+        sobject: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> SFClientResult<DeletedRecords> {
+        replication::check_window(start, end, &Utc::now()).map_err(
+            SFClientError::InvalidWindow,
+        )?;
+        self.replication_window(
+            sobject,
+            start,
+            end,
+            &replication::deleted,
+            DeletedRecords::merge,
+        )
+    }
+
+    /// Fetches a replication window, splitting a window over the Id limit in two and merging the
+    /// results of its halves
+    fn replication_window<T, F>(
+        &mut self,
+        sobject: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        fetch: &F,
+        merge: fn(T, T) -> T,
+    ) -> SFClientResult<T>
+    where
+        F: Fn(&RestRequest, &str, &DateTime<Utc>, &DateTime<Utc>) -> RestResult<T>,
+    {
+        let fetched = self.rest_once(|request| fetch(request, sobject, start, end));
+
+        match fetched {
+            Err(ref err) if error_code(err) == Some(replication::EXCEEDED_ID_LIMIT) => {
+                let middle = replication::split_window(start, end).ok_or_else(|| {
+                    SFClientError::IdLimitExceeded(format!(
+                        "More than 600,000 {} records changed between {} and {}",
+                        sobject,
+                        start,
+                        end
+                    ))
+                })?;
+                info!("Too many {} records changed, splitting the window at {}", sobject, middle);
+
+                let earlier = self.replication_window(sobject, start, &middle, fetch, merge)?;
+                let later = self.replication_window(sobject, &middle, end, fetch, merge)?;
+                Ok(merge(earlier, later))
+            }
+            fetched => fetched.map_err(replication_failure),
+        }
+    }
+
+    /// Streams a blob field, such as `ContentVersion.VersionData`, into `writer`, returning the
+    /// number of bytes written. The download is not retried once it has started.
+    pub fn download_blob<W: Write>(
+        &mut self,
+        sobject: &str,
+        id: &str,
+        field: &str,
+        writer: &mut W,
+    ) -> SFClientResult<u64> {
+        self.rest_at_most_once(|request| {
+            blob::download(request, sobject, id, field, &mut *writer)
+        })
+    }
+
+    /// Creates a `ContentVersion`, `Attachment` or `Document` with `metadata` as its fields and
+    /// the contents of `blob` streamed into `blob_field`, for example `VersionData` or `Body`.
+    /// Passing the blob's `length` checks it against the 2GB `ContentVersion` or 500MB limit and
+    /// sends a `Content-Length` rather than a chunked body.
+    pub fn upload_blob<T, R>(
+        &mut self,
+        sobject: &str,
+        blob_field: &str,
+        metadata: &T,
+        file_name: &str,
+        blob: R,
+        length: Option<u64>,
+    ) -> SFClientResult<SaveResult>
+    where
+        T: Serialize,
+        R: Read + Send + 'static,
+    {
+        self.rest_once(|request| {
+            blob::upload(request, sobject, blob_field, metadata, file_name, blob, length)
+        })
+    }
+
+    /// Lists every sObject available to the user along with its capabilities
+    pub fn describe_global(&mut self) -> SFClientResult<DescribeGlobal> {
+        self.rest(describe::describe_global)
+    }
+
+    pub fn describe(&mut self, sobject: &str) -> SFClientResult<SObjectDescribe> {
+        match self.describe_cache.take() {
+            Some(mut cache) => {
+                let described = self.rest(|request| {
+                    describe_cache::cached_describe(request, &mut cache, sobject)
+                });
+                self.describe_cache = Some(cache);
+                described
+            }
+            None => self.rest(|request| describe::describe(request, sobject)),
+        }
+    }
+
+    /// Lists the API versions supported by the instance
+    pub fn versions(&mut self) -> SFClientResult<Vec<ApiVersion>> {
+        self.rest(versions::get_versions)
+    }
+}
+
+pub type SFClientResult<T> = Result<T, SFClientError>;
+
+/// Sleeps until the next poll of `job_id`, or fails once `timeout` has passed since `started`
+fn wait_to_poll(
+    job_id: &str,
+    started: Instant,
+    interval: Duration,
+    timeout: Duration,
+) -> SFClientResult<()> {
+    let elapsed = started.elapsed();
+
+    if elapsed >= timeout {
+        return Err(SFClientError::Timeout(
+            format!("Bulk job {} did not finish within {:?}", job_id, timeout),
+        ));
+    }
+
+    thread::sleep(interval.min(timeout - elapsed));
+    Ok(())
+}
+
+/// The API error code of the first error a call failed with
+fn error_code(err: &SFClientError) -> Option<&str> {
+    match *err {
+        SFClientError::Rest(RestError::API(ref failure)) => {
+            failure.errors.first().map(|error| error.error_code.as_str())
+        }
+        _ => None,
+    }
+}
+
+/// Reports a replication window starting before the data the instance still holds as such
+fn replication_failure(err: SFClientError) -> SFClientError {
+    if error_code(&err) == Some(replication::INVALID_REPLICATION_DATE) {
+        SFClientError::ReplicationDateUnavailable(err.to_string())
+    } else {
+        err
+    }
 }
 
-pub type SFClientResult<T> = Result<T, SFClientError>;
+/// Whether the API turned down the access token, which calls for authenticating again
+fn token_rejected(err: &SFClientError) -> bool {
+    match *err {
+        SFClientError::Query(QueryError::API(ref failure)) => failure.error_code == 401,
+        SFClientError::Rest(ref failure) => failure.status() == Some(401),
+        _ => false,
+    }
+}
 
 #[derive(Debug)]
 pub enum SFClientError {
     InvalidLoginUrl,
     InvalidVersion,
+    UnsupportedVersion(String),
     ClientBuildFailure(ClientError),
     Token(TokenError),
     Query(QueryError),
+    Rest(RestError),
     TokenUnavailable,
+    UsageThresholdReached(ApiUsage),
+    JobFailed(JobInfo),
+    BatchFailed(BulkBatchInfo),
+    InvalidWindow(String),
+    IdLimitExceeded(String),
+    ReplicationDateUnavailable(String),
+    Timeout(String),
     Network(ClientError),
 }
 
@@ -175,10 +1174,40 @@ impl fmt::Display for SFClientError {
             SFClientError::InvalidVersion => {
                 write!(f, "Supplied version is not a valid API version")
             }
+            SFClientError::UnsupportedVersion(ref version) => {
+                write!(f, "API version {} is not supported by the instance", version)
+            }
             SFClientError::ClientBuildFailure(ref err) => err.fmt(f),
             SFClientError::Token(ref err) => err.fmt(f),
             SFClientError::Query(ref err) => err.fmt(f),
+            SFClientError::Rest(ref err) => err.fmt(f),
             SFClientError::TokenUnavailable => write!(f, "Failed to get token from the API"),
+            SFClientError::UsageThresholdReached(ref usage) => {
+                write!(f, "API usage threshold has been reached: {}", usage)
+            }
+            SFClientError::JobFailed(ref job) => {
+                write!(
+                    f,
+                    "Bulk job {} ended as {:?}: {}",
+                    job.id,
+                    job.state,
+                    job.error_message.as_ref().map_or("", |message| message.as_str())
+                )
+            }
+            SFClientError::InvalidWindow(ref reason) => write!(f, "{}", reason),
+            SFClientError::IdLimitExceeded(ref reason) => write!(f, "{}", reason),
+            SFClientError::ReplicationDateUnavailable(ref reason) => {
+                write!(f, "Window starts before the earliest available date: {}", reason)
+            }
+            SFClientError::Timeout(ref reason) => write!(f, "{}", reason),
+            SFClientError::BatchFailed(ref batch) => {
+                write!(
+                    f,
+                    "Bulk batch {} failed: {}",
+                    batch.id,
+                    batch.state_message.as_ref().map_or("", |message| message.as_str())
+                )
+            }
             SFClientError::Network(ref err) => err.fmt(f),
         }
     }
@@ -189,10 +1218,21 @@ impl Error for SFClientError {
         match *self {
             SFClientError::InvalidLoginUrl => "Supplied login url is not a valid login url",
             SFClientError::InvalidVersion => "Supplied version is not a valid API version",
+            SFClientError::UnsupportedVersion(_) => "API version is not supported by the instance",
             SFClientError::ClientBuildFailure(ref err) => err.description(),
             SFClientError::Token(ref err) => err.description(),
             SFClientError::Query(ref err) => err.description(),
+            SFClientError::Rest(ref err) => err.description(),
             SFClientError::TokenUnavailable => "Failed to get token from the API",
+            SFClientError::UsageThresholdReached(_) => "API usage threshold has been reached",
+            SFClientError::JobFailed(_) => "Bulk job failed or was aborted",
+            SFClientError::BatchFailed(_) => "Bulk batch failed",
+            SFClientError::InvalidWindow(ref reason) => reason.as_str(),
+            SFClientError::IdLimitExceeded(ref reason) => reason.as_str(),
+            SFClientError::ReplicationDateUnavailable(_) => {
+                "Window starts before the earliest available date"
+            }
+            SFClientError::Timeout(ref reason) => reason.as_str(),
             SFClientError::Network(ref err) => err.description(),
         }
     }
@@ -201,26 +1241,92 @@ impl Error for SFClientError {
         match *self {
             SFClientError::InvalidLoginUrl => None,
             SFClientError::InvalidVersion => None,
+            SFClientError::UnsupportedVersion(_) => None,
             SFClientError::ClientBuildFailure(ref err) => Some(err),
             SFClientError::Token(ref err) => Some(err),
             SFClientError::Query(ref err) => Some(err),
+            SFClientError::Rest(ref err) => Some(err),
             SFClientError::TokenUnavailable => None,
+            SFClientError::UsageThresholdReached(_) => None,
+            SFClientError::JobFailed(_) => None,
+            SFClientError::BatchFailed(_) => None,
+            SFClientError::InvalidWindow(_) => None,
+            SFClientError::IdLimitExceeded(_) => None,
+            SFClientError::ReplicationDateUnavailable(_) => None,
+            SFClientError::Timeout(_) => None,
             SFClientError::Network(ref err) => Some(err),
         }
     }
 }
 
+/// A collection call that failed part way, along with the results of the chunks that had already
+/// been committed, in input order
+#[derive(Debug)]
+pub struct CollectionError<T = SaveResult> {
+    pub completed: Vec<T>,
+    pub error: SFClientError,
+}
+
+pub type CollectionResult<T = SaveResult> = Result<Vec<T>, CollectionError<T>>;
+
+impl<T> fmt::Display for CollectionError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} after {} records were processed", self.error, self.completed.len())
+    }
+}
+
+impl<T: fmt::Debug> Error for CollectionError<T> {
+    fn description(&self) -> &str {
+        self.error.description()
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&self.error)
+    }
+}
+
+impl From<RestError> for SFClientError {
+    fn from(err: RestError) -> SFClientError {
+        match err {
+            RestError::Network(net_failure) => SFClientError::Network(net_failure),
+            error => SFClientError::Rest(error),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use chrono::{DateTime, Duration, Utc};
     use mockito;
     use mockito::{mock, Mock};
     use serde_json;
 
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration as StdDuration;
+
+    use {IncomingResponse, Interceptor};
+    use ApiUsage;
     use SFClient;
+    use VersionPolicy;
+    use {CompositeRequest, ContentType, Subrequest};
+    use {IngestJobRequest, JobState, Operation};
     use SFClientError;
+    use {ApiFailure, RestError, RestFailure};
+    use replication::{split_window, window_path};
+    use replication_failure;
+    use limits::Limit;
     use query::{API_BASE, QueryResponse};
     use token::TokenResponse;
 
+    #[derive(Debug, Deserialize)]
+    struct QueryRow {
+        #[serde(rename = "Id")]
+        _id: String,
+        #[serde(rename = "Name")]
+        name: String,
+    }
+
     const ACCESS: &'static str = "00Dx0000000BV7z!AR8AQAxo9UfVkh8AlV0Gomt9Czx9LjHnSSpwBMmbRcgKFmxOtvxjTrKW19ye6PE3Ds1eQz3z8jr3W7_VbWmEu4Q8TVGSTHxs";
 
     macro_rules! test_client {
@@ -294,6 +1400,17 @@ mod tests {
         m
     }
 
+    /// Announces more of a body than it sends, so the call fails after the server has acted on it
+    fn dropped_response_mock(method: &str, path: &str) -> Mock {
+        let mut m = mock(method, path);
+        m.with_status(200)
+            .with_header("content-length", "1024")
+            .with_body("{");
+        m.expect(1);
+        m.create();
+        m
+    }
+
     fn query_success() -> String {
         let resp = json!({
             "total_size": 1,
@@ -393,6 +1510,284 @@ mod tests {
         a_mock.remove();
     }
 
+    #[test]
+    fn test_does_not_repeat_failed_creates() {
+        let mut c_mock = mock("POST", "/instance/services/data/v20.0/sobjects/Lead");
+        c_mock
+            .with_status(400)
+            .with_body(r#"[{"message": "Required fields are missing", "errorCode": "REQUIRED_FIELD_MISSING"}]"#);
+        c_mock.expect(1);
+        c_mock.create();
+        let mut client = test_client!(auth_url("failed_create"), 3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let res = client.create("Lead", &json!({"Company": "Acme"}));
+
+        c_mock.assert();
+        c_mock.remove();
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_does_not_repeat_deletes_that_timed_out() {
+        let mut d_mock = mock("DELETE", "/instance/services/data/v20.0/sobjects/Lead/00QD");
+        d_mock.with_status(504).with_body("");
+        d_mock.expect(1);
+        d_mock.create();
+        let mut client = test_client!(auth_url("timed_out_delete"), 3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let res = client.delete("Lead", "00QD");
+
+        d_mock.assert();
+        d_mock.remove();
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_does_not_resend_composite_requests_after_dropped_responses() {
+        let c_mock = dropped_response_mock("POST", "/instance/services/data/v20.0/composite");
+        let mut client = test_client!(auth_url("dropped_composite"), 3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+        let mut composite = CompositeRequest::new(true);
+        composite.push(Subrequest::new(
+            "POST",
+            "NewAccount",
+            "/sobjects/Account",
+            Some(json!({"Name": "Acme"})),
+        ));
+
+        let res = client.composite(&composite);
+
+        c_mock.assert();
+        c_mock.remove();
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_does_not_resend_bulk_batches_after_dropped_responses() {
+        let b_mock = dropped_response_mock("POST", "/instance/services/async/20.0/job/750D/batch");
+        let mut client = test_client!(auth_url("dropped_batch"), 3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let res = client.add_bulk_batch("750D", ContentType::Csv, b"Name\nAcme\n");
+
+        b_mock.assert();
+        b_mock.remove();
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_keeps_committed_chunks_when_a_collection_call_fails() {
+        let mut client = test_client!(auth_url("collection_chunks"), 0);
+        let items: Vec<usize> = (0..250).collect();
+
+        let res = client.chunked(&items, |_, chunk| if chunk[0] == 0 {
+            Ok(chunk.to_vec())
+        } else {
+            Err(SFClientError::TokenUnavailable)
+        });
+
+        match res {
+            Err(err) => {
+                assert_eq!(200, err.completed.len());
+                assert_eq!(199, err.completed[199]);
+            }
+            Ok(_) => panic!("Failed to report the failed chunk"),
+        };
+    }
+
+    #[test]
+    fn test_reauthenticates_downloads_with_invalid_token() {
+        let a_mock = auth_mock(auth_path("download_invalid_token"), 200, auth_success());
+        let path = "/instance/services/data/v20.0/sobjects/Attachment/00PD/Body";
+        let mut invalid_mock = mock("GET", path);
+        invalid_mock
+            .with_status(401)
+            .with_body(r#"[{"message": "Session expired", "errorCode": "INVALID_SESSION_ID"}]"#)
+            .match_header("Authorization", "Bearer invalid");
+        invalid_mock.create();
+        let mut d_mock = mock("GET", path);
+        d_mock.with_status(200).with_body("notes").match_header(
+            "Authorization",
+            ("Bearer ".to_owned() + ACCESS).as_str(),
+        );
+        d_mock.create();
+        let mut client = test_client!(auth_url("download_invalid_token"), 1);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("invalid", "", instance_url.as_str(), "", ""));
+        let mut output = vec![];
+
+        let res = client.download_blob("Attachment", "00PD", "Body", &mut output);
+
+        a_mock.remove();
+        invalid_mock.remove();
+        d_mock.remove();
+
+        assert_eq!(5, res.unwrap());
+        assert_eq!(b"notes".to_vec(), output);
+    }
+
+    #[test]
+    fn test_drops_rejected_token_of_single_attempt_calls() {
+        let mut u_mock = mock("PUT", "/instance/services/data/v20.0/jobs/ingest/750R/batches");
+        u_mock.with_status(401).with_body(
+            r#"[{"message": "Session expired", "errorCode": "INVALID_SESSION_ID"}]"#,
+        );
+        u_mock.create();
+        let mut client = test_client!(auth_url("single_attempt"), 3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("invalid", "", instance_url.as_str(), "", ""));
+
+        let res = client.upload_ingest_reader("750R", Cursor::new(b"Name\nAcme\n".to_vec()));
+
+        u_mock.remove();
+
+        assert!(res.is_err());
+        assert!(client.token().is_none());
+    }
+
+    #[test]
+    fn test_reports_failed_ingest_jobs() {
+        let mut j_mock = mock("GET", "/instance/services/data/v20.0/jobs/ingest/750F");
+        j_mock.with_status(200).with_body(
+            json!({
+                "id": "750F",
+                "operation": "insert",
+                "object": "Account",
+                "state": "Failed",
+                "errorMessage": "InvalidBatch : Field name not found : Nmae"
+            }).to_string()
+                .as_str(),
+        );
+        j_mock.create();
+        let mut client = test_client!(auth_url("failed_ingest"), 0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let res = client.wait_for_ingest_job(
+            "750F",
+            StdDuration::from_millis(10),
+            StdDuration::from_millis(30),
+        );
+
+        j_mock.remove();
+
+        match res {
+            Err(SFClientError::JobFailed(job)) => assert_eq!(JobState::Failed, job.state),
+            _ => panic!("Failed to report a failed ingest job"),
+        };
+    }
+
+    #[test]
+    fn test_stops_waiting_for_jobs_after_timeout() {
+        let mut j_mock = mock("GET", "/instance/services/data/v20.0/jobs/query/750W");
+        j_mock.with_status(200).with_body(
+            json!({"id": "750W", "operation": "query", "object": "Account", "state": "InProgress"})
+                .to_string()
+                .as_str(),
+        );
+        j_mock.create();
+        let mut client = test_client!(auth_url("job_timeout"), 0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let res = client.wait_for_query_job(
+            "750W",
+            StdDuration::from_millis(10),
+            StdDuration::from_millis(30),
+        );
+
+        j_mock.remove();
+
+        match res {
+            Err(SFClientError::Timeout(_)) => (),
+            _ => panic!("Failed to give up on a job that did not finish"),
+        };
+    }
+
+    #[test]
+    fn test_splits_deleted_windows_over_the_id_limit() {
+        let start = Utc::now() - Duration::days(1);
+        let end = start + Duration::hours(2);
+        let middle = split_window(&start, &end).unwrap();
+        let path = |start: &DateTime<Utc>, end: &DateTime<Utc>| {
+            "/instance/services/data/v20.0".to_owned() +
+                window_path("Lead", "deleted", start, end).as_str()
+        };
+        let deleted = |ids: &[&str]| {
+            let records: Vec<_> = ids.iter()
+                .map(|id| json!({"id": id, "deletedDate": "2017-11-01T10:00:00.000+0000"}))
+                .collect();
+
+            json!({
+                "deletedRecords": records,
+                "earliestDateAvailable": "2017-10-05T00:00:00.000+0000",
+                "latestDateCovered": "2017-11-01T23:45:00.000+0000"
+            }).to_string()
+        };
+        let mut whole = mock("GET", path(&start, &end).as_str());
+        whole
+            .with_status(400)
+            .with_body(r#"[{"message": "Too many Ids", "errorCode": "EXCEEDED_ID_LIMIT"}]"#);
+        whole.expect(1);
+        whole.create();
+        let mut earlier = mock("GET", path(&start, &middle).as_str());
+        earlier.with_status(200).with_body(deleted(&["00QA", "00QB"]).as_str());
+        earlier.create();
+        let mut later = mock("GET", path(&middle, &end).as_str());
+        later.with_status(200).with_body(deleted(&["00QB", "00QC"]).as_str());
+        later.create();
+        let mut client = test_client!(auth_url("deleted_split"), 3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let res = client.deleted_records("Lead", &start, &end);
+
+        whole.assert();
+        whole.remove();
+        earlier.remove();
+        later.remove();
+
+        let ids: Vec<String> = res.unwrap()
+            .deleted_records
+            .into_iter()
+            .map(|record| record.id)
+            .collect();
+        assert_eq!(vec!["00QA", "00QB", "00QC"], ids);
+    }
+
+    #[test]
+    fn test_reports_windows_before_earliest_available_date() {
+        let err = SFClientError::Rest(RestError::API(RestFailure {
+            status: 400,
+            errors: vec![
+                ApiFailure {
+                    message: "startDate is before the earliest available date".to_owned(),
+                    error_code: "INVALID_REPLICATION_DATE".to_owned(),
+                    ..ApiFailure::default()
+                },
+            ],
+        }));
+
+        match replication_failure(err) {
+            SFClientError::ReplicationDateUnavailable(_) => (),
+            _ => panic!("Failed to recognise a window before the earliest available date"),
+        };
+        match replication_failure(SFClientError::TokenUnavailable) {
+            SFClientError::TokenUnavailable => (),
+            _ => panic!("Changed an unrelated failure"),
+        };
+    }
+
     #[test]
     fn test_calls_query() {
         let a_mock = auth_mock(auth_path("query_test"), 200, auth_success());
@@ -416,4 +1811,319 @@ mod tests {
             Err(err) => panic!("Query call test failed {:?}", err),
         };
     }
+
+    struct PathRecorder {
+        paths: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Interceptor for PathRecorder {
+        fn on_response(&self, response: &IncomingResponse) {
+            self.paths.lock().unwrap().push(response.request.url.clone());
+        }
+    }
+
+    #[test]
+    fn test_interceptors_see_token_and_query_calls() {
+        let a_mock = auth_mock(auth_path("intercepted"), 200, auth_success());
+        let q_mock = query_mock(
+            query_path("intercepted", "v20.0"),
+            200,
+            query_success(),
+            ACCESS,
+        );
+        let paths = Arc::new(Mutex::new(vec![]));
+        let mut client = test_client!(auth_url("intercepted"), 0);
+        client.add_interceptor(PathRecorder { paths: paths.clone() });
+
+        client.query("intercepted");
+
+        a_mock.remove();
+        q_mock.remove();
+
+        assert_eq!(
+            vec![
+                auth_url("intercepted"),
+                mockito::SERVER_URL.to_owned() + query_path("intercepted", "v20.0").as_str(),
+            ],
+            *paths.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_debug_output_hides_credentials() {
+        let mut client = SFClient::new(
+            "http://127.0.0.1",
+            "v20.0",
+            "c_id",
+            "super-client-secret",
+            "user",
+            "super-password",
+        ).unwrap();
+        client.set_token(TokenResponse::new(ACCESS, "Bearer", "", "", ""));
+
+        let output = format!("{:?}", client);
+
+        assert!(!output.contains("super-client-secret"));
+        assert!(!output.contains("super-password"));
+        assert!(!output.contains(ACCESS));
+    }
+
+    fn usage_mock(url: String, usage: &str) -> Mock {
+        let mut m = mock("GET", url.as_str());
+        m.with_status(200)
+            .with_body(query_success().as_str())
+            .with_header("Sforce-Limit-Info", usage);
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_records_api_usage() {
+        let q_mock = usage_mock(query_path("usage", "v20.0"), "api-usage=18/5000");
+        let mut client = test_client!(auth_url("usage"), 0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        client.query("usage").unwrap();
+
+        q_mock.remove();
+
+        assert_eq!(Some(ApiUsage::new(18, 5000)), client.api_usage());
+    }
+
+    #[test]
+    fn test_refuses_calls_past_usage_threshold() {
+        let mut q_mock = usage_mock(query_path("usage_threshold", "v20.0"), "api-usage=18/5000");
+        q_mock.expect(1);
+        let mut client = test_client!(auth_url("usage_threshold"), 0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+        client.set_usage_threshold(Some(10));
+
+        client.query("usage_threshold").unwrap();
+        let res = client.query("usage_threshold");
+
+        q_mock.assert();
+        q_mock.remove();
+
+        match res {
+            Err(SFClientError::UsageThresholdReached(usage)) => {
+                assert_eq!(ApiUsage::new(18, 5000), usage)
+            }
+            _ => panic!("Failed to refuse a call past the usage threshold"),
+        };
+    }
+
+    #[test]
+    fn test_reauthenticates_rest_calls_with_invalid_token() {
+        let a_mock = auth_mock(auth_path("rest_invalid_token"), 200, auth_success());
+        let mut invalid_mock = mock("GET", "/instance/services/data/v20.0/limits");
+        invalid_mock
+            .with_status(401)
+            .with_body(r#"[{"message": "Session expired", "errorCode": "INVALID_SESSION_ID"}]"#)
+            .match_header("Authorization", "Bearer invalid");
+        invalid_mock.create();
+        let mut l_mock = mock("GET", "/instance/services/data/v20.0/limits");
+        l_mock
+            .with_status(200)
+            .with_body(r#"{"DailyApiRequests": {"Max": 15000, "Remaining": 14998}}"#)
+            .match_header("Authorization", ("Bearer ".to_owned() + ACCESS).as_str());
+        l_mock.create();
+        let mut client = test_client!(auth_url("rest_invalid_token"), 1);
+
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("invalid", "", instance_url.as_str(), "", ""));
+        let res = client.limits();
+
+        a_mock.remove();
+        invalid_mock.remove();
+        l_mock.remove();
+
+        assert_eq!(
+            Some(&Limit {
+                max: 15000,
+                remaining: 14998,
+            }),
+            res.unwrap().daily_api_requests()
+        );
+        assert_eq!(ACCESS, client.token().unwrap().access());
+    }
+
+    fn versions_mock() -> Mock {
+        let mut m = mock("GET", "/instance/services/data/");
+        m.with_status(200).with_body(
+            json!([
+                {"label": "Winter '11", "url": "/services/data/v20.0", "version": "20.0"},
+                {"label": "Spring '11", "url": "/services/data/v21.0", "version": "21.0"}
+            ]).to_string()
+                .as_str(),
+        );
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_negotiates_latest_version() {
+        let v_mock = versions_mock();
+        let q_mock = query_mock(
+            query_path("latest_version", "v21.0"),
+            200,
+            query_success(),
+            ACCESS,
+        );
+        let mut client = test_client!(auth_url("latest_version"), 0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+        client.set_version_policy(VersionPolicy::Latest);
+
+        let res = client.query("latest_version");
+
+        v_mock.remove();
+        q_mock.remove();
+
+        assert!(res.is_ok());
+        assert_eq!("v21.0", client.version());
+    }
+
+    #[test]
+    fn test_reauthenticates_when_negotiation_is_rejected() {
+        let a_mock = auth_mock(auth_path("negotiation_rejected"), 200, auth_success());
+        let mut invalid_mock = mock("GET", "/instance/services/data/");
+        invalid_mock
+            .with_status(401)
+            .with_body(r#"[{"message": "Session expired", "errorCode": "INVALID_SESSION_ID"}]"#)
+            .match_header("Authorization", "Bearer stale");
+        invalid_mock.create();
+        let v_mock = versions_mock();
+        let q_mock = query_mock(
+            query_path("negotiation_rejected", "v21.0"),
+            200,
+            query_success(),
+            ACCESS,
+        );
+        let mut client = test_client!(auth_url("negotiation_rejected"), 1);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("stale", "", instance_url.as_str(), "", ""));
+        client.set_version_policy(VersionPolicy::Latest);
+
+        let res = client.query("negotiation_rejected");
+
+        a_mock.remove();
+        invalid_mock.remove();
+        v_mock.remove();
+        q_mock.remove();
+
+        assert!(res.is_ok());
+        assert_eq!(ACCESS, client.token().unwrap().access());
+    }
+
+    #[test]
+    fn test_rejects_unsupported_version() {
+        let v_mock = versions_mock();
+        let mut client = SFClient::new(
+            auth_url("unsupported_version").as_str(),
+            "v99.0",
+            "id",
+            "secret",
+            "user",
+            "pass",
+        ).unwrap();
+        client.set_attempt_limit(0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+        client.set_version_policy(VersionPolicy::Verify);
+
+        let res = client.query("unsupported_version");
+
+        v_mock.remove();
+
+        match res {
+            Err(SFClientError::UnsupportedVersion(version)) => assert_eq!("v99.0", version),
+            _ => panic!("Failed to reject an unsupported version"),
+        };
+    }
+
+    #[test]
+    fn test_ingests_csv_through_job() {
+        let job = |state: &str| {
+            json!({"id": "750I", "operation": "insert", "object": "Account", "state": state})
+                .to_string()
+        };
+        let mut c_mock = mock("POST", "/instance/services/data/v20.0/jobs/ingest");
+        c_mock.with_status(200).with_body(job("Open").as_str());
+        c_mock.create();
+        let mut u_mock = mock("PUT", "/instance/services/data/v20.0/jobs/ingest/750I/batches");
+        u_mock.with_status(201).with_body("").expect(1);
+        u_mock.create();
+        let mut p_mock = mock("PATCH", "/instance/services/data/v20.0/jobs/ingest/750I");
+        p_mock.with_status(200).with_body(job("UploadComplete").as_str());
+        p_mock.create();
+        let mut client = test_client!(auth_url("ingest_csv"), 0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let jobs = client.ingest_csv(
+            &IngestJobRequest::new("Account", Operation::Insert),
+            "Name\nFirst\nSecond\n".as_bytes(),
+        );
+
+        c_mock.remove();
+        p_mock.remove();
+        u_mock.assert();
+        u_mock.remove();
+
+        let jobs = jobs.unwrap();
+        assert_eq!(1, jobs.len());
+        assert_eq!(JobState::UploadComplete, jobs[0].state);
+    }
+
+    #[test]
+    fn test_reads_query_job_rows_across_pages() {
+        let path = "/instance/services/data/v20.0/jobs/query/750Q/results";
+        let mut first = mock("GET", path);
+        first
+            .with_status(200)
+            .with_header("Sforce-Locator", "MTAwMDA")
+            .with_header("Sforce-NumberOfRecords", "2")
+            .with_body("\"Id\",\"Name\"\n\"001A\",\"First\"\n\"001B\",\"Second\"\n");
+        first.create();
+        let mut second = mock("GET", (path.to_owned() + "?locator=MTAwMDA").as_str());
+        second
+            .with_status(200)
+            .with_header("Sforce-Locator", "null")
+            .with_header("Sforce-NumberOfRecords", "1")
+            .with_body("\"Id\",\"Name\"\n\"001C\",\"Third\"\n");
+        second.create();
+        let mut client = test_client!(auth_url("query_job_rows"), 0);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new(ACCESS, "", instance_url.as_str(), "", ""));
+
+        let rows = client
+            .query_job_rows::<QueryRow>("750Q")
+            .collect::<Result<Vec<QueryRow>, SFClientError>>();
+        let mut output = vec![];
+        let records = client.query_job_results("750Q", &mut output);
+
+        first.remove();
+        second.remove();
+
+        let names: Vec<String> = rows.unwrap().into_iter().map(|row| row.name).collect();
+        assert_eq!(vec!["First", "Second", "Third"], names);
+        assert_eq!(3, records.unwrap());
+        assert_eq!(
+            "\"Id\",\"Name\"\n\"001A\",\"First\"\n\"001B\",\"Second\"\n\"001C\",\"Third\"\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rejects_replication_windows_past_thirty_days() {
+        let mut client = test_client!(auth_url("replication_window"), 0);
+        let end = Utc::now();
+
+        match client.updated_records("Account", &(end - Duration::days(45)), &end) {
+            Err(SFClientError::InvalidWindow(_)) => (),
+            _ => panic!("Failed to reject a window older than 30 days"),
+        };
+    }
 }
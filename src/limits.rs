@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Iter;
+
+use rest::{RestRequest, RestResult};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Limit {
+    #[serde(rename = "Max")]
+    pub max: i64,
+    #[serde(rename = "Remaining")]
+    pub remaining: i64,
+}
+
+impl Limit {
+    pub fn used(&self) -> i64 {
+        self.max - self.remaining
+    }
+}
+
+/// Every limit reported by the org, keyed by the name the API uses, e.g. `DailyApiRequests`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Limits(BTreeMap<String, Limit>);
+
+impl Limits {
+    pub fn get(&self, name: &str) -> Option<&Limit> {
+        self.0.get(name)
+    }
+
+    pub fn iter(&self) -> Iter<String, Limit> {
+        self.0.iter()
+    }
+
+    pub fn daily_api_requests(&self) -> Option<&Limit> {
+        self.get("DailyApiRequests")
+    }
+
+    pub fn daily_bulk_api_requests(&self) -> Option<&Limit> {
+        self.get("DailyBulkApiRequests")
+    }
+
+    pub fn data_storage_mb(&self) -> Option<&Limit> {
+        self.get("DataStorageMB")
+    }
+
+    pub fn file_storage_mb(&self) -> Option<&Limit> {
+        self.get("FileStorageMB")
+    }
+}
+
+pub fn get_limits(request: &RestRequest) -> RestResult<Limits> {
+    request.get(request.url("/limits").as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use http::HttpClient;
+    use limits::{Limit, get_limits};
+    use rest::RestRequest;
+
+    #[test]
+    fn test_parses_limits() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!({
+            "DailyApiRequests": {
+                "Max": 15000,
+                "Remaining": 14998,
+                "Ant Migration Tool": {"Max": 0, "Remaining": 0}
+            },
+            "DataStorageMB": {"Max": 5, "Remaining": 5}
+        });
+
+        let mut m = mock("GET", "/services/data/vXY.Z/limits");
+        m.with_status(200).with_body(body.to_string().as_str());
+        m.create();
+
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let limits = get_limits(&request).unwrap();
+
+        m.remove();
+
+        assert_eq!(
+            Some(&Limit {
+                max: 15000,
+                remaining: 14998,
+            }),
+            limits.daily_api_requests()
+        );
+        assert_eq!(2, limits.daily_api_requests().unwrap().used());
+        assert_eq!(None, limits.daily_bulk_api_requests());
+        assert_eq!(5, limits.data_storage_mb().unwrap().remaining);
+    }
+}
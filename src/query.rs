@@ -1,19 +1,32 @@
-use reqwest::{Client, Error as ClientError, RequestBuilder, StatusCode};
-use reqwest::header::{Authorization, Bearer};
+use reqwest::{Error as ClientError, Method, StatusCode};
+use reqwest::header::{Authorization, Bearer, Headers};
 use serde_json::Value;
 
 use std::error::Error;
 use std::fmt;
 
+use http::HttpClient;
+
 pub static API_BASE: &'static str = "services/data/";
 
-#[derive(Debug)]
 pub struct QueryRequest<'a, 'b, 'c, 'd, 'e> {
     endpoint: &'a str,
     version: &'b str,
     query: &'c str,
     token: &'d str,
-    client: &'e Client,
+    client: &'e HttpClient,
+}
+
+impl<'a, 'b, 'c, 'd, 'e> fmt::Debug for QueryRequest<'a, 'b, 'c, 'd, 'e> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("QueryRequest")
+            .field("endpoint", &self.endpoint)
+            .field("version", &self.version)
+            .field("query", &self.query)
+            .field("token", &"[REDACTED]")
+            .field("client", &self.client)
+            .finish()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -49,7 +62,7 @@ impl<'a, 'b, 'c, 'd, 'e> QueryRequest<'a, 'b, 'c, 'd, 'e> {
         version: &'b str,
         query: &'c str,
         token: &'d str,
-        client: &'e Client,
+        client: &'e HttpClient,
     ) -> QueryRequest<'a, 'b, 'c, 'd, 'e> {
         QueryRequest {
             endpoint: endpoint,
@@ -60,17 +73,21 @@ impl<'a, 'b, 'c, 'd, 'e> QueryRequest<'a, 'b, 'c, 'd, 'e> {
         }
     }
 
-    fn build_request(&self) -> RequestBuilder {
-        let url = self.endpoint.to_owned() + API_BASE + self.version + "/query?q=" + self.query;
-        self.client.get(url.as_str()).header(Authorization(Bearer {
-            token: self.token.to_string(),
-        }))
+    fn url(&self) -> String {
+        self.endpoint.to_owned() + API_BASE + self.version + "/query?q=" + self.query
+    }
 
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.set(Authorization(Bearer { token: self.token.to_string() }));
+        headers
     }
 
     pub fn send(&self) -> QueryResult {
-        self.build_request()
-            .send()
+        self.client
+            .send(Method::Get, self.url().as_str(), self.headers(), |request| {
+                request
+            })
             .map_err(QueryError::Network)
             .and_then(|mut response| match *response.status() {
                 StatusCode::Ok => {
@@ -133,11 +150,11 @@ impl Error for QueryError {
 mod tests {
     use mockito;
     use mockito::{mock, Mock};
-    use reqwest::Client;
     use serde_json;
 
     use QueryRequest;
     use QueryResponse;
+    use http::HttpClient;
 
     const API_BASE: &'static str = "services/data/";
     const VERSION: &'static str = "vXY.Z";
@@ -161,7 +178,7 @@ mod tests {
 
     #[test]
     fn test_handles_successful_query() {
-        let client = Client::new().unwrap();
+        let client = HttpClient::new().unwrap();
         let ep = mockito::SERVER_URL.to_owned() + "/";
         let query = "query_success";
         let resp = QueryResponse {
@@ -182,4 +199,12 @@ mod tests {
 
         assert_eq!(resp, req.send().unwrap());
     }
+
+    #[test]
+    fn test_debug_output_hides_token() {
+        let client = HttpClient::new().unwrap();
+        let req = QueryRequest::new("https://na1.salesforce.com/", VERSION, "debug", ACCESS, &client);
+
+        assert!(!format!("{:?}", req).contains(ACCESS));
+    }
 }
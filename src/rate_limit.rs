@@ -0,0 +1,192 @@
+use std::fmt;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A token bucket shared by every request a client sends, including authentication. Wrap it in
+/// an `Arc` to have several clients draw from the same bucket.
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    max_in_flight: Option<usize>,
+    state: Mutex<BucketState>,
+    released: Condvar,
+}
+
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+    in_flight: usize,
+}
+
+/// Held while a request is in flight, dropping it frees up the slot for the next request
+pub struct Permit<'a> {
+    limiter: &'a RateLimiter,
+    waited: Option<Duration>,
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("rate", &self.rate)
+            .field("burst", &self.burst)
+            .field("max_in_flight", &self.max_in_flight)
+            .finish()
+    }
+}
+
+impl RateLimiter {
+    /// Allows `requests_per_second` requests on average, with bursts of up to one second's worth.
+    /// Panics unless the rate is finite and greater than zero.
+    pub fn new(requests_per_second: f64) -> RateLimiter {
+        assert!(
+            requests_per_second.is_finite() && requests_per_second > 0.0,
+            "Rate must be finite and greater than zero, got {}",
+            requests_per_second
+        );
+
+        let burst = requests_per_second.max(1.0);
+
+        RateLimiter {
+            rate: requests_per_second,
+            burst: burst,
+            max_in_flight: None,
+            state: Mutex::new(BucketState {
+                tokens: burst,
+                refilled_at: Instant::now(),
+                in_flight: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> RateLimiter {
+        self.burst = (burst as f64).max(1.0);
+        self.state.get_mut().unwrap().tokens = self.burst;
+        self
+    }
+
+    /// Caps the requests waiting on a response. A request stops counting once its response
+    /// headers arrive, so a body that is still being streamed, such as a Bulk API result or a
+    /// blob download, does not hold a slot.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> RateLimiter {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Blocks until a request may be sent
+    pub fn acquire(&self) -> Permit {
+        let started = Instant::now();
+        let mut blocked = false;
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(max_in_flight) = self.max_in_flight {
+                while state.in_flight >= max_in_flight {
+                    blocked = true;
+                    state = self.released.wait(state).unwrap();
+                }
+            }
+
+            let now = Instant::now();
+            let refill = seconds(now.duration_since(state.refilled_at)) * self.rate;
+            state.tokens = (state.tokens + refill).min(self.burst);
+            state.refilled_at = now;
+
+            if state.tokens >= 1.0 {
+                state.tokens -= 1.0;
+                state.in_flight += 1;
+                break;
+            }
+
+            let wait = duration((1.0 - state.tokens) / self.rate);
+            blocked = true;
+
+            drop(state);
+            thread::sleep(wait);
+            state = self.state.lock().unwrap();
+        }
+
+        Permit {
+            limiter: self,
+            waited: if blocked { Some(started.elapsed()) } else { None },
+        }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        self.released.notify_one();
+    }
+}
+
+impl<'a> Permit<'a> {
+    /// How long the caller was held back, if it had to wait at all
+    pub fn waited(&self) -> Option<Duration> {
+        self.waited
+    }
+}
+
+impl<'a> Drop for Permit<'a> {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+fn duration(seconds: f64) -> Duration {
+    let whole = seconds.trunc();
+    Duration::new(whole as u64, ((seconds - whole) * 1_000_000_000.0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use rate_limit::RateLimiter;
+
+    #[test]
+    fn test_allows_burst_without_waiting() {
+        let limiter = RateLimiter::new(10.0).with_burst(2);
+
+        assert_eq!(None, limiter.acquire().waited());
+        assert_eq!(None, limiter.acquire().waited());
+    }
+
+    #[test]
+    fn test_waits_once_bucket_is_empty() {
+        let limiter = RateLimiter::new(20.0).with_burst(1);
+
+        limiter.acquire();
+        let waited = limiter.acquire().waited().expect(
+            "Second request should have waited",
+        );
+
+        assert!(waited >= Duration::from_millis(40));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_zero_rate() {
+        RateLimiter::new(0.0);
+    }
+
+    #[test]
+    fn test_limits_requests_in_flight() {
+        let limiter = Arc::new(RateLimiter::new(1000.0).with_max_in_flight(1));
+        let held = limiter.acquire();
+
+        let waiting = limiter.clone();
+        let handle = thread::spawn(move || waiting.acquire().waited().is_some());
+
+        thread::sleep(Duration::from_millis(50));
+        drop(held);
+
+        assert!(handle.join().unwrap());
+    }
+}
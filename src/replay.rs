@@ -0,0 +1,113 @@
+use serde_json;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+/// Remembers the last event handled on each streaming channel so that a new subscription can
+/// resume after it instead of missing events or replaying the whole retention window
+pub trait ReplayStore: Send {
+    fn load(&self, channel: &str) -> Option<i64>;
+
+    fn save(&mut self, channel: &str, replay_id: i64) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryReplayStore {
+    replay_ids: BTreeMap<String, i64>,
+}
+
+impl MemoryReplayStore {
+    pub fn new() -> MemoryReplayStore {
+        MemoryReplayStore::default()
+    }
+}
+
+impl ReplayStore for MemoryReplayStore {
+    fn load(&self, channel: &str) -> Option<i64> {
+        self.replay_ids.get(channel).cloned()
+    }
+
+    fn save(&mut self, channel: &str, replay_id: i64) -> io::Result<()> {
+        self.replay_ids.insert(channel.to_owned(), replay_id);
+        Ok(())
+    }
+}
+
+/// Keeps replay ids in a json file, which is rewritten through a temporary file on every save so
+/// that a crash never leaves it half written
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileReplayStore {
+    path: PathBuf,
+    replay_ids: BTreeMap<String, i64>,
+}
+
+impl FileReplayStore {
+    /// Opens the store at `path`, starting empty when the file does not exist yet
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<FileReplayStore> {
+        let path = path.into();
+
+        let replay_ids = match File::open(&path) {
+            Ok(file) => {
+                serde_json::from_reader(file).map_err(|err| {
+                    io::Error::new(ErrorKind::InvalidData, err)
+                })?
+            }
+            Err(ref err) if err.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(FileReplayStore {
+            path: path,
+            replay_ids: replay_ids,
+        })
+    }
+}
+
+impl ReplayStore for FileReplayStore {
+    fn load(&self, channel: &str) -> Option<i64> {
+        self.replay_ids.get(channel).cloned()
+    }
+
+    fn save(&mut self, channel: &str, replay_id: i64) -> io::Result<()> {
+        self.replay_ids.insert(channel.to_owned(), replay_id);
+
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let staged = self.path.with_extension("tmp");
+        let file = File::create(&staged)?;
+        serde_json::to_writer(file, &self.replay_ids).map_err(|err| {
+            io::Error::new(ErrorKind::Other, err)
+        })?;
+
+        fs::rename(staged, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use replay::{FileReplayStore, ReplayStore};
+
+    #[test]
+    fn test_file_store_survives_reopening() {
+        let path = env::temp_dir().join("micro_sf_client_replay_test").join("replay.json");
+
+        let mut store = FileReplayStore::open(path.clone()).unwrap();
+        assert_eq!(None, store.load("/data/ChangeEvents"));
+        store.save("/data/ChangeEvents", 42).unwrap();
+        store.save("/data/AccountChangeEvent", 7).unwrap();
+
+        let reopened = FileReplayStore::open(path.clone()).unwrap();
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        assert_eq!(Some(42), reopened.load("/data/ChangeEvents"));
+        assert_eq!(Some(7), reopened.load("/data/AccountChangeEvent"));
+    }
+}
@@ -0,0 +1,246 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as DeError;
+
+use std::collections::HashSet;
+
+use rest::{RestRequest, RestResult, encode_query};
+use sobject::sobject_path;
+
+/// How far back the replication calls can look
+pub const REPLICATION_DAYS: i64 = 30;
+
+/// The error code of an `updated` window holding more than 600,000 Ids
+pub static EXCEEDED_ID_LIMIT: &'static str = "EXCEEDED_ID_LIMIT";
+/// The error code of a window starting before the earliest date the instance still reports on
+pub static INVALID_REPLICATION_DATE: &'static str = "INVALID_REPLICATION_DATE";
+
+static DATE_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%.f%z";
+
+fn deserialize_date<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+
+    DateTime::parse_from_str(text.as_str(), DATE_FORMAT)
+        .map(|date| date.with_timezone(&Utc))
+        .map_err(D::Error::custom)
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatedRecords {
+    pub ids: Vec<String>,
+    /// Changes up to this time are covered, use it as the start of the next window
+    #[serde(deserialize_with = "deserialize_date")]
+    pub latest_date_covered: DateTime<Utc>,
+}
+
+impl UpdatedRecords {
+    /// Combines the results of two adjoining windows, `later` starting where this one ends
+    pub fn merge(mut self, later: UpdatedRecords) -> UpdatedRecords {
+        let seen: HashSet<String> = self.ids.iter().cloned().collect();
+        self.ids.extend(later.ids.into_iter().filter(|id| !seen.contains(id)));
+        self.latest_date_covered = later.latest_date_covered;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedRecord {
+    pub id: String,
+    #[serde(deserialize_with = "deserialize_date")]
+    pub deleted_date: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedRecords {
+    pub deleted_records: Vec<DeletedRecord>,
+    /// Deletions before this time have been purged and can no longer be reported
+    #[serde(deserialize_with = "deserialize_date")]
+    pub earliest_date_available: DateTime<Utc>,
+    #[serde(deserialize_with = "deserialize_date")]
+    pub latest_date_covered: DateTime<Utc>,
+}
+
+impl DeletedRecords {
+    /// Combines the results of two adjoining windows, `later` starting where this one ends and
+    /// carrying the most recent earliest available date
+    pub fn merge(mut self, later: DeletedRecords) -> DeletedRecords {
+        let seen: HashSet<String> = self.deleted_records
+            .iter()
+            .map(|record| record.id.clone())
+            .collect();
+        self.deleted_records.extend(later.deleted_records.into_iter().filter(
+            |record| !seen.contains(&record.id),
+        ));
+        self.earliest_date_available = later.earliest_date_available;
+        self.latest_date_covered = later.latest_date_covered;
+        self
+    }
+}
+
+/// Checks a window against the rules of the replication calls, which only look back 30 days
+pub fn check_window(
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    now: &DateTime<Utc>,
+) -> Result<(), String> {
+    if start >= end {
+        Err(format!("Window start {} is not before its end {}", start, end))
+    } else if *start < *now - Duration::days(REPLICATION_DAYS) {
+        Err(format!(
+            "Window start {} is more than {} days ago",
+            start,
+            REPLICATION_DAYS
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Splits a window in two, or gives `None` when it is too short to split as the API only resolves
+/// windows to the minute
+pub fn split_window(start: &DateTime<Utc>, end: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if *end - *start >= Duration::minutes(2) {
+        Some(*start + (*end - *start) / 2)
+    } else {
+        None
+    }
+}
+
+/// The path of an `updated` or `deleted` call, which `kind` names, for a window
+pub fn window_path(sobject: &str, kind: &str, start: &DateTime<Utc>, end: &DateTime<Utc>) -> String {
+    let format = "%Y-%m-%dT%H:%M:%S+00:00";
+
+    sobject_path(sobject) + "/" + kind + "/?start=" +
+        encode_query(start.format(format).to_string().as_str()).as_str() + "&end=" +
+        encode_query(end.format(format).to_string().as_str()).as_str()
+}
+
+/// Lists the Ids of `sobject` records changed between `start` and `end`
+pub fn updated(
+    request: &RestRequest,
+    sobject: &str,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> RestResult<UpdatedRecords> {
+    let path = window_path(sobject, "updated", start, end);
+    request.get(request.url(path.as_str()).as_str())
+}
+
+/// Lists the `sobject` records deleted between `start` and `end`
+pub fn deleted(
+    request: &RestRequest,
+    sobject: &str,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> RestResult<DeletedRecords> {
+    let path = window_path(sobject, "deleted", start, end);
+    request.get(request.url(path.as_str()).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use mockito;
+    use mockito::mock;
+
+    use http::HttpClient;
+    use replication::{UpdatedRecords, check_window, deleted, split_window, updated};
+    use rest::RestRequest;
+
+    #[test]
+    fn test_checks_windows() {
+        let now = Utc.ymd(2017, 11, 30).and_hms(12, 0, 0);
+
+        assert!(check_window(&(now - Duration::days(1)), &now, &now).is_ok());
+        assert!(check_window(&now, &(now - Duration::days(1)), &now).is_err());
+        assert!(check_window(&(now - Duration::days(31)), &now, &now).is_err());
+    }
+
+    #[test]
+    fn test_splits_and_merges_windows() {
+        let start = Utc.ymd(2017, 11, 1).and_hms(0, 0, 0);
+        let middle = split_window(&start, &Utc.ymd(2017, 11, 2).and_hms(0, 0, 0)).unwrap();
+        let earlier = UpdatedRecords {
+            ids: vec!["001A".to_owned(), "001B".to_owned()],
+            latest_date_covered: middle,
+        };
+        let later = UpdatedRecords {
+            ids: vec!["001B".to_owned(), "001C".to_owned()],
+            latest_date_covered: Utc.ymd(2017, 11, 2).and_hms(0, 0, 0),
+        };
+
+        assert_eq!(Utc.ymd(2017, 11, 1).and_hms(12, 0, 0), middle);
+        assert_eq!(None, split_window(&start, &(start + Duration::seconds(90))));
+
+        let merged = earlier.merge(later);
+        assert_eq!(vec!["001A", "001B", "001C"], merged.ids);
+        assert_eq!(Utc.ymd(2017, 11, 2).and_hms(0, 0, 0), merged.latest_date_covered);
+    }
+
+    #[test]
+    fn test_lists_updated_ids() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock(
+            "GET",
+            "/services/data/vXY.Z/sobjects/Account/updated/?start=2017-11-01T00%3A00%3A00%2B00%3A00&end=2017-11-02T00%3A00%3A00%2B00%3A00",
+        );
+        m.with_status(200).with_body(
+            r#"{"ids": ["001A", "001B"], "latestDateCovered": "2017-11-01T23:45:00.000+0000"}"#,
+        );
+        m.create();
+
+        let res = updated(
+            &request,
+            "Account",
+            &Utc.ymd(2017, 11, 1).and_hms(0, 0, 0),
+            &Utc.ymd(2017, 11, 2).and_hms(0, 0, 0),
+        );
+
+        m.remove();
+
+        let res = res.unwrap();
+        assert_eq!(vec!["001A", "001B"], res.ids);
+        assert_eq!(Utc.ymd(2017, 11, 1).and_hms(23, 45, 0), res.latest_date_covered);
+    }
+
+    #[test]
+    fn test_lists_deleted_records() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock(
+            "GET",
+            "/services/data/vXY.Z/sobjects/Contact/deleted/?start=2017-11-01T00%3A00%3A00%2B00%3A00&end=2017-11-02T00%3A00%3A00%2B00%3A00",
+        );
+        m.with_status(200).with_body(
+            json!({
+                "deletedRecords": [{"id": "003D", "deletedDate": "2017-11-01T10:00:00.000+0000"}],
+                "earliestDateAvailable": "2017-10-05T00:00:00.000+0000",
+                "latestDateCovered": "2017-11-01T23:45:00.000+0000"
+            }).to_string()
+                .as_str(),
+        );
+        m.create();
+
+        let res = deleted(
+            &request,
+            "Contact",
+            &Utc.ymd(2017, 11, 1).and_hms(0, 0, 0),
+            &Utc.ymd(2017, 11, 2).and_hms(0, 0, 0),
+        );
+
+        m.remove();
+
+        let res = res.unwrap();
+        assert_eq!("003D", res.deleted_records[0].id);
+        assert_eq!(Utc.ymd(2017, 10, 5).and_hms(0, 0, 0), res.earliest_date_available);
+    }
+}
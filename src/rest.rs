@@ -0,0 +1,365 @@
+use reqwest::{Error as ClientError, Method, RequestBuilder, Response};
+use reqwest::header::{Authorization, Bearer, Headers};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::str;
+
+use url::form_urlencoded::byte_serialize;
+use url::percent_encoding::{PATH_SEGMENT_ENCODE_SET, utf8_percent_encode};
+
+use http::HttpClient;
+use query::API_BASE;
+
+/// An authenticated call against the REST API of a single instance. Paths passed to `url` are
+/// resolved beneath the same versioned base path that queries use.
+pub struct RestRequest<'a> {
+    endpoint: &'a str,
+    version: &'a str,
+    token: &'a str,
+    client: &'a HttpClient,
+}
+
+impl<'a> fmt::Debug for RestRequest<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RestRequest")
+            .field("endpoint", &self.endpoint)
+            .field("version", &self.version)
+            .field("token", &"[REDACTED]")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiFailure {
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub error_code: String,
+    #[serde(default)]
+    pub status_code: String,
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RestFailure {
+    pub status: u16,
+    pub errors: Vec<ApiFailure>,
+}
+
+impl fmt::Display for RestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error [{}]", self.status)?;
+
+        for error in &self.errors {
+            let code = match (error.error_code.is_empty(), error.status_code.is_empty()) {
+                (false, false) => error.error_code.clone() + "/" + error.status_code.as_str(),
+                (false, true) => error.error_code.clone(),
+                _ => error.status_code.clone(),
+            };
+            write!(f, " {} : {}", code, error.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> RestRequest<'a> {
+    pub fn new(
+        endpoint: &'a str,
+        version: &'a str,
+        token: &'a str,
+        client: &'a HttpClient,
+    ) -> RestRequest<'a> {
+        RestRequest {
+            endpoint: endpoint,
+            version: version,
+            token: token,
+            client: client,
+        }
+    }
+
+    pub fn version(&self) -> &str {
+        self.version
+    }
+
+    pub fn token(&self) -> &str {
+        self.token
+    }
+
+    /// A url beneath `services/data/{version}`, `path` should start with a `/`
+    pub fn url(&self, path: &str) -> String {
+        self.endpoint.to_owned() + API_BASE + self.version + path
+    }
+
+    /// A url relative to the root of the instance
+    pub fn instance_url(&self, path: &str) -> String {
+        self.endpoint.to_owned() + path
+    }
+
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+        headers.set(Authorization(Bearer { token: self.token.to_string() }));
+        headers
+    }
+
+    pub fn send<F>(&self, method: Method, url: &str, headers: Headers, body: F) -> RestResult<Response>
+    where
+        F: FnOnce(RequestBuilder) -> RequestBuilder,
+    {
+        let mut all_headers = self.headers();
+        all_headers.extend(headers.iter());
+
+        self.client.send(method, url, all_headers, body).map_err(
+            RestError::Network,
+        )
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, url: &str) -> RestResult<T> {
+        self.send(Method::Get, url, Headers::new(), |request| request)
+            .and_then(parse_response)
+    }
+
+    pub fn send_json<B, T>(&self, method: Method, url: &str, body: &B) -> RestResult<T>
+    where
+        B: Serialize,
+        T: DeserializeOwned,
+    {
+        self.send(method, url, Headers::new(), |request| request.json(body))
+            .and_then(parse_response)
+    }
+
+    pub fn delete(&self, url: &str) -> RestResult<()> {
+        self.send(Method::Delete, url, Headers::new(), |request| request)
+            .and_then(expect_success)
+            .map(|_| ())
+    }
+}
+
+/// Encodes a value so that it can be used as a single segment of a url path
+pub fn encode_segment(value: &str) -> String {
+    utf8_percent_encode(value, PATH_SEGMENT_ENCODE_SET).to_string()
+}
+
+/// Encodes a value so that it can be passed as a query string parameter
+pub fn encode_query(value: &str) -> String {
+    byte_serialize(value.as_bytes()).collect()
+}
+
+pub fn parse_response<T: DeserializeOwned>(mut response: Response) -> RestResult<T> {
+    if response.status().is_success() {
+        response.json::<T>().or_else(
+            |_| Err(RestError::ResponseParseFailure),
+        )
+    } else {
+        Err(failure(response))
+    }
+}
+
+/// The first value of a response header, when it is present and valid utf8
+pub fn header(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get_raw(name)
+        .and_then(|raw| raw.one())
+        .and_then(|value| str::from_utf8(value).ok())
+        .map(|value| value.to_owned())
+}
+
+pub fn expect_success(response: Response) -> RestResult<Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(failure(response))
+    }
+}
+
+/// Reads the error list the API returns alongside a failed status. Bodies that can not be
+/// understood still produce a failure carrying the status code.
+pub fn failure(mut response: Response) -> RestError {
+    let status = response.status().to_u16();
+    let mut content = String::new();
+    let _ = response.read_to_string(&mut content);
+
+    failure_from(status, content.as_str())
+}
+
+/// Builds a failure from a body that has already been read
+pub fn failure_from(status: u16, content: &str) -> RestError {
+    let errors = serde_json::from_str::<Vec<ApiFailure>>(content)
+        .or_else(|_| serde_json::from_str::<ApiFailure>(content).map(|error| vec![error]))
+        .unwrap_or_default();
+
+    RestError::API(RestFailure {
+        status: status,
+        errors: errors,
+    })
+}
+
+#[derive(Debug)]
+pub enum RestError {
+    API(RestFailure),
+    DuplicateExternalId(Vec<String>),
+    InvalidRequest(String),
+    ResponseParseFailure,
+    Io(io::Error),
+    Network(ClientError),
+}
+
+pub type RestResult<T> = Result<T, RestError>;
+
+impl RestError {
+    pub fn status(&self) -> Option<u16> {
+        match *self {
+            RestError::API(ref failure) => Some(failure.status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RestError::API(ref failure) => write!(f, "{}", failure),
+            RestError::DuplicateExternalId(ref matches) => {
+                write!(f, "External id matched {} records: {:?}", matches.len(), matches)
+            }
+            RestError::InvalidRequest(ref reason) => write!(f, "Invalid request: {}", reason),
+            RestError::ResponseParseFailure => write!(f, "Failed to parse the response from the API"),
+            RestError::Io(ref err) => err.fmt(f),
+            RestError::Network(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for RestError {
+    fn description(&self) -> &str {
+        match *self {
+            RestError::API(_) => "api_failure",
+            RestError::DuplicateExternalId(_) => "duplicate_external_id",
+            RestError::InvalidRequest(_) => "invalid_request",
+            RestError::ResponseParseFailure => "response_parse_failed",
+            RestError::Io(ref err) => err.description(),
+            RestError::Network(ref err) => err.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            RestError::Io(ref err) => Some(err),
+            RestError::Network(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::{mock, Mock};
+
+    use http::HttpClient;
+    use rest::{ApiFailure, RestError, RestFailure, RestRequest, encode_query, encode_segment};
+
+    const VERSION: &'static str = "vXY.Z";
+    const ACCESS: &'static str = "test-token";
+
+    fn rest_mock(method: &str, path: &str, code: usize, body: &str) -> Mock {
+        let mut m = mock(method, path);
+        m.with_status(code).with_body(body).match_header(
+            "Authorization",
+            "Bearer test-token",
+        );
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_builds_versioned_urls() {
+        let client = HttpClient::new().unwrap();
+        let request = RestRequest::new("https://na1.salesforce.com/", VERSION, ACCESS, &client);
+
+        assert_eq!(
+            "https://na1.salesforce.com/services/data/vXY.Z/limits",
+            request.url("/limits")
+        );
+    }
+
+    #[test]
+    fn test_debug_output_hides_token() {
+        let client = HttpClient::new().unwrap();
+        let request = RestRequest::new("https://na1.salesforce.com/", VERSION, ACCESS, &client);
+
+        assert!(!format!("{:?}", request).contains(ACCESS));
+    }
+
+    #[test]
+    fn test_encodes_path_segments() {
+        assert_eq!("ext%2F12%20a%3F", encode_segment("ext/12 a?"));
+        assert_eq!("EXT-12", encode_segment("EXT-12"));
+    }
+
+    #[test]
+    fn test_encodes_query_values() {
+        assert_eq!(
+            "SELECT+Id+FROM+Account+WHERE+Name+%3D+%27A%26B%27",
+            encode_query("SELECT Id FROM Account WHERE Name = 'A&B'")
+        );
+    }
+
+    #[test]
+    fn test_separates_error_codes() {
+        let failure = RestFailure {
+            status: 400,
+            errors: vec![
+                ApiFailure {
+                    message: "Bad value".to_owned(),
+                    error_code: "INVALID_FIELD".to_owned(),
+                    status_code: "FIELD_INTEGRITY_EXCEPTION".to_owned(),
+                    ..ApiFailure::default()
+                },
+            ],
+        };
+
+        assert_eq!(
+            "Error [400] INVALID_FIELD/FIELD_INTEGRITY_EXCEPTION : Bad value",
+            failure.to_string()
+        );
+    }
+
+    #[test]
+    fn test_parses_error_list() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!([{
+            "message": "Session expired or invalid",
+            "errorCode": "INVALID_SESSION_ID"
+        }]);
+        let mock = rest_mock(
+            "GET",
+            "/services/data/vXY.Z/rest_error",
+            401,
+            body.to_string().as_str(),
+        );
+        let request = RestRequest::new(ep.as_str(), VERSION, ACCESS, &client);
+
+        let res = request.get::<ApiFailure>(request.url("/rest_error").as_str());
+
+        mock.remove();
+
+        match res {
+            Err(RestError::API(failure)) => {
+                assert_eq!(401, failure.status);
+                assert_eq!("INVALID_SESSION_ID", failure.errors[0].error_code);
+            }
+            _ => panic!("Failed to parse the error list"),
+        };
+    }
+}
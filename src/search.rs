@@ -0,0 +1,200 @@
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use std::collections::BTreeMap;
+
+use rest::{RestError, RestRequest, RestResult, encode_query};
+
+/// The records a search matched, across every sObject type it covered
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SearchResponse {
+    pub search_records: Vec<Value>,
+}
+
+impl SearchResponse {
+    /// Older API versions answer with a bare array of records instead of an object
+    fn from_value(value: Value) -> RestResult<SearchResponse> {
+        match value {
+            Value::Array(records) => Ok(SearchResponse { search_records: records }),
+            value => serde_json::from_value(value).map_err(|_| RestError::ResponseParseFailure),
+        }
+    }
+
+    /// Groups the records by the sObject type named in their `attributes`
+    pub fn grouped(&self) -> BTreeMap<String, Vec<Value>> {
+        let mut groups = BTreeMap::new();
+
+        for record in &self.search_records {
+            let sobject = record
+                .pointer("/attributes/type")
+                .and_then(|sobject| sobject.as_str())
+                .unwrap_or("")
+                .to_owned();
+            groups.entry(sobject).or_insert_with(Vec::new).push(record.clone());
+        }
+
+        groups
+    }
+
+    /// Deserializes the records of one sObject type
+    pub fn records_of<T: DeserializeOwned>(&self, sobject: &str) -> RestResult<Vec<T>> {
+        self.search_records
+            .iter()
+            .filter(|record| {
+                record.pointer("/attributes/type").and_then(|sobject| sobject.as_str()) ==
+                    Some(sobject)
+            })
+            .map(|record| {
+                serde_json::from_value(record.clone()).map_err(|_| RestError::ResponseParseFailure)
+            })
+            .collect()
+    }
+}
+
+/// The fields and filters to apply to one sObject type of a parameterized search
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSObject {
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    #[serde(rename = "where", skip_serializing_if = "Option::is_none")]
+    pub where_clause: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+impl SearchSObject {
+    pub fn new<S: Into<String>>(name: S, fields: &[&str]) -> SearchSObject {
+        SearchSObject {
+            name: name.into(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            ..SearchSObject::default()
+        }
+    }
+}
+
+/// A search built from parameters rather than a SOSL statement, so search text needs no escaping
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParameterizedSearch {
+    pub q: String,
+    #[serde(rename = "in", skip_serializing_if = "Option::is_none")]
+    pub in_fields: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sobjects: Vec<SearchSObject>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overall_limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_limit: Option<u32>,
+}
+
+impl ParameterizedSearch {
+    pub fn new<S: Into<String>>(q: S) -> ParameterizedSearch {
+        ParameterizedSearch {
+            q: q.into(),
+            ..ParameterizedSearch::default()
+        }
+    }
+
+    pub fn sobject(mut self, sobject: SearchSObject) -> ParameterizedSearch {
+        self.sobjects.push(sobject);
+        self
+    }
+}
+
+/// Runs a SOSL statement such as `FIND {Acme} IN NAME FIELDS RETURNING Account(Id, Name)`
+pub fn search(request: &RestRequest, sosl: &str) -> RestResult<SearchResponse> {
+    let path = "/search/?q=".to_owned() + encode_query(sosl).as_str();
+
+    request.get::<Value>(request.url(path.as_str()).as_str()).and_then(
+        SearchResponse::from_value,
+    )
+}
+
+/// Runs a search described by parameters, which can be larger than a SOSL statement fits in a url
+pub fn parameterized_search(
+    request: &RestRequest,
+    search: &ParameterizedSearch,
+) -> RestResult<SearchResponse> {
+    request
+        .send_json::<_, Value>(
+            Method::Post,
+            request.url("/parameterizedSearch/").as_str(),
+            search,
+        )
+        .and_then(SearchResponse::from_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use http::HttpClient;
+    use rest::RestRequest;
+    use search::{ParameterizedSearch, SearchSObject, parameterized_search, search};
+
+    #[derive(Debug, Deserialize)]
+    struct Account {
+        #[serde(rename = "Id")]
+        id: String,
+    }
+
+    fn search_body() -> String {
+        json!({
+            "searchRecords": [
+                {"attributes": {"type": "Account", "url": "/services/data/v41.0/sobjects/Account/001A"}, "Id": "001A"},
+                {"attributes": {"type": "Contact", "url": "/services/data/v41.0/sobjects/Contact/003C"}, "Id": "003C"},
+                {"attributes": {"type": "Account", "url": "/services/data/v41.0/sobjects/Account/001B"}, "Id": "001B"}
+            ]
+        }).to_string()
+    }
+
+    #[test]
+    fn test_groups_sosl_results_by_type() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock(
+            "GET",
+            "/services/data/vXY.Z/search/?q=FIND+%7BAcme%7D+RETURNING+Account%2C+Contact",
+        );
+        m.with_status(200).with_body(search_body().as_str());
+        m.create();
+
+        let response = search(&request, "FIND {Acme} RETURNING Account, Contact");
+
+        m.remove();
+
+        let response = response.unwrap();
+        let groups = response.grouped();
+        assert_eq!(2, groups["Account"].len());
+        assert_eq!(1, groups["Contact"].len());
+
+        let accounts = response.records_of::<Account>("Account").unwrap();
+        assert_eq!("001B", accounts[1].id);
+    }
+
+    #[test]
+    fn test_runs_parameterized_search() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let mut m = mock("POST", "/services/data/vXY.Z/parameterizedSearch/");
+        m.with_status(200).with_body(search_body().as_str());
+        m.create();
+
+        let parameters =
+            ParameterizedSearch::new("Acme").sobject(SearchSObject::new("Account", &["Id", "Name"]));
+        let response = parameterized_search(&request, &parameters);
+
+        m.remove();
+
+        assert_eq!(3, response.unwrap().search_records.len());
+    }
+}
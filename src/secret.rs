@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// Holds a credential or access token and keeps it out of `Debug` output
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new<S: Into<String>>(value: S) -> Secret {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret([REDACTED])")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use secret::Secret;
+
+    #[test]
+    fn test_debug_hides_value() {
+        let secret = Secret::new("hunter2");
+
+        assert_eq!("Secret([REDACTED])", format!("{:?}", secret));
+        assert_eq!("hunter2", secret.expose());
+    }
+
+    #[test]
+    fn test_serializes_as_plain_string() {
+        let secret = serde_json::from_str::<Secret>("\"hunter2\"").unwrap();
+
+        assert_eq!("hunter2", secret.expose());
+        assert_eq!("\"hunter2\"", serde_json::to_string(&secret).unwrap());
+    }
+}
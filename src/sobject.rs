@@ -0,0 +1,263 @@
+use reqwest::{Method, StatusCode};
+use reqwest::header::Headers;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use rest::{ApiFailure, RestError, RestRequest, RestResult, encode_segment, expect_success,
+           failure};
+
+/// The outcome of writing a single record
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SaveResult {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub success: bool,
+    #[serde(default)]
+    pub errors: Vec<ApiFailure>,
+}
+
+/// Whether an upsert inserted a new record or updated an existing one
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpsertResult {
+    Created(String),
+    Updated(Option<String>),
+}
+
+pub fn sobject_path(sobject: &str) -> String {
+    "/sobjects/".to_owned() + sobject
+}
+
+pub fn record_path(sobject: &str, id: &str) -> String {
+    sobject_path(sobject) + "/" + id
+}
+
+pub fn create<T: Serialize>(request: &RestRequest, sobject: &str, record: &T) -> RestResult<SaveResult> {
+    request.send_json(
+        Method::Post,
+        request.url(sobject_path(sobject).as_str()).as_str(),
+        record,
+    )
+}
+
+/// Fetches a single record, limited to `fields` when any are given
+pub fn retrieve<T: DeserializeOwned>(
+    request: &RestRequest,
+    sobject: &str,
+    id: &str,
+    fields: &[&str],
+) -> RestResult<T> {
+    let mut path = record_path(sobject, id);
+
+    if !fields.is_empty() {
+        path = path + "?fields=" + fields.join(",").as_str();
+    }
+
+    request.get(request.url(path.as_str()).as_str())
+}
+
+pub fn update<T: Serialize>(request: &RestRequest, sobject: &str, id: &str, record: &T) -> RestResult<()> {
+    request
+        .send(
+            Method::Patch,
+            request.url(record_path(sobject, id).as_str()).as_str(),
+            Headers::new(),
+            |builder| builder.json(record),
+        )
+        .and_then(expect_success)
+        .map(|_| ())
+}
+
+pub fn upsert<T: Serialize>(
+    request: &RestRequest,
+    sobject: &str,
+    field: &str,
+    value: &str,
+    record: &T,
+) -> RestResult<UpsertResult> {
+    let path = sobject_path(sobject) + "/" + field + "/" + encode_segment(value).as_str();
+    let mut response = request.send(
+        Method::Patch,
+        request.url(path.as_str()).as_str(),
+        Headers::new(),
+        |builder| builder.json(record),
+    )?;
+
+    match *response.status() {
+        StatusCode::Created => {
+            response
+                .json::<SaveResult>()
+                .map(|result| UpsertResult::Created(result.id.unwrap_or_default()))
+                .or_else(|_| Err(RestError::ResponseParseFailure))
+        }
+        StatusCode::Ok => {
+            response
+                .json::<SaveResult>()
+                .map(|result| UpsertResult::Updated(result.id))
+                .or_else(|_| Err(RestError::ResponseParseFailure))
+        }
+        StatusCode::NoContent => Ok(UpsertResult::Updated(None)),
+        StatusCode::MultipleChoices => {
+            let matches = response.json::<Vec<String>>().or_else(|_| {
+                Err(RestError::ResponseParseFailure)
+            })?;
+
+            Err(RestError::DuplicateExternalId(matches))
+        }
+        _ => Err(failure(response)),
+    }
+}
+
+pub fn delete(request: &RestRequest, sobject: &str, id: &str) -> RestResult<()> {
+    request.delete(request.url(record_path(sobject, id).as_str()).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::{mock, Mock};
+    use serde_json::Value;
+
+    use http::HttpClient;
+    use rest::{RestError, RestRequest};
+    use sobject::{SaveResult, UpsertResult, create, delete, retrieve, update, upsert};
+
+    const VERSION: &'static str = "vXY.Z";
+    const ACCESS: &'static str = "test-token";
+
+    fn sobject_mock(method: &str, path: &str, code: usize, body: &str) -> Mock {
+        let mut m = mock(method, ("/services/data/vXY.Z".to_owned() + path).as_str());
+        m.with_status(code).with_body(body).match_header(
+            "Authorization",
+            "Bearer test-token",
+        );
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_creates_record() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let mock = sobject_mock(
+            "POST",
+            "/sobjects/CreateTest",
+            201,
+            r#"{"id": "001D000000IqhSLIAZ", "errors": [], "success": true}"#,
+        );
+        let request = RestRequest::new(ep.as_str(), VERSION, ACCESS, &client);
+
+        let res = create(&request, "CreateTest", &json!({"Name": "Test"}));
+
+        mock.remove();
+
+        assert_eq!(
+            SaveResult {
+                id: Some("001D000000IqhSLIAZ".to_owned()),
+                success: true,
+                errors: vec![],
+            },
+            res.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_retrieves_record_fields() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let mock = sobject_mock(
+            "GET",
+            "/sobjects/RetrieveTest/001D000000IqhSLIAZ?fields=Id,Name",
+            200,
+            r#"{"Id": "001D000000IqhSLIAZ", "Name": "Test"}"#,
+        );
+        let request = RestRequest::new(ep.as_str(), VERSION, ACCESS, &client);
+
+        let res = retrieve::<Value>(&request, "RetrieveTest", "001D000000IqhSLIAZ", &["Id", "Name"]);
+
+        mock.remove();
+
+        assert_eq!(json!({"Id": "001D000000IqhSLIAZ", "Name": "Test"}), res.unwrap());
+    }
+
+    #[test]
+    fn test_updates_and_deletes_record() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let update_mock = sobject_mock("PATCH", "/sobjects/WriteTest/001D000000IqhSLIAZ", 204, "");
+        let delete_mock = sobject_mock(
+            "DELETE",
+            "/sobjects/WriteTest/001D000000IqhSLIAZ",
+            404,
+            r#"[{"message": "entity is deleted", "errorCode": "ENTITY_IS_DELETED", "fields": []}]"#,
+        );
+        let request = RestRequest::new(ep.as_str(), VERSION, ACCESS, &client);
+
+        let updated = update(
+            &request,
+            "WriteTest",
+            "001D000000IqhSLIAZ",
+            &json!({"Name": "Updated"}),
+        );
+        let deleted = delete(&request, "WriteTest", "001D000000IqhSLIAZ");
+
+        update_mock.remove();
+        delete_mock.remove();
+
+        assert!(updated.is_ok());
+        match deleted {
+            Err(RestError::API(failure)) => {
+                assert_eq!(404, failure.status);
+                assert_eq!("ENTITY_IS_DELETED", failure.errors[0].error_code);
+            }
+            _ => panic!("Failed to report a failed delete"),
+        };
+    }
+
+    #[test]
+    fn test_upserts_by_encoded_external_id() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let created_mock = sobject_mock(
+            "PATCH",
+            "/sobjects/UpsertTest/Ext_Id__c/ORD%2F1%201",
+            201,
+            r#"{"id": "001D000000IqhSLIAZ", "errors": [], "success": true}"#,
+        );
+        let updated_mock = sobject_mock("PATCH", "/sobjects/UpsertTest/Ext_Id__c/ORD-2", 204, "");
+        let request = RestRequest::new(ep.as_str(), VERSION, ACCESS, &client);
+
+        let created = upsert(&request, "UpsertTest", "Ext_Id__c", "ORD/1 1", &json!({}));
+        let updated = upsert(&request, "UpsertTest", "Ext_Id__c", "ORD-2", &json!({}));
+
+        created_mock.remove();
+        updated_mock.remove();
+
+        assert_eq!(
+            UpsertResult::Created("001D000000IqhSLIAZ".to_owned()),
+            created.unwrap()
+        );
+        assert_eq!(UpsertResult::Updated(None), updated.unwrap());
+    }
+
+    #[test]
+    fn test_upsert_reports_duplicate_external_ids() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let mock = sobject_mock(
+            "PATCH",
+            "/sobjects/DuplicateTest/Ext_Id__c/ORD-3",
+            300,
+            r#"["/services/data/vXY.Z/sobjects/Account/001A", "/services/data/vXY.Z/sobjects/Account/001B"]"#,
+        );
+        let request = RestRequest::new(ep.as_str(), VERSION, ACCESS, &client);
+
+        let res = upsert(&request, "DuplicateTest", "Ext_Id__c", "ORD-3", &json!({}));
+
+        mock.remove();
+
+        match res {
+            Err(RestError::DuplicateExternalId(matches)) => assert_eq!(2, matches.len()),
+            _ => panic!("Failed to report duplicate external ids"),
+        };
+    }
+}
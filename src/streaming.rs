@@ -0,0 +1,665 @@
+use reqwest::{Method, Response};
+use reqwest::header::Headers;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Map, Value};
+
+use std::collections::{BTreeMap, VecDeque};
+use std::thread;
+use std::time::Duration;
+
+use replay::ReplayStore;
+use rest::{ApiFailure, RestError, RestFailure, RestRequest, RestResult, failure};
+use {SFClient, SFClientError, SFClientResult};
+
+/// Replay only the events published after subscribing
+pub const REPLAY_NEW: i64 = -1;
+/// Replay every event still retained by the instance
+pub const REPLAY_ALL: i64 = -2;
+
+static COMETD_BASE: &'static str = "cometd/";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Advice {
+    pub reconnect: Option<String>,
+    pub interval: Option<u64>,
+    pub timeout: Option<u64>,
+}
+
+/// A Bayeux message, either a reply on a `/meta` channel or an event delivered by `connect`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Message {
+    pub channel: String,
+    pub client_id: Option<String>,
+    pub successful: Option<bool>,
+    pub error: Option<String>,
+    pub subscription: Option<String>,
+    pub advice: Option<Advice>,
+    pub data: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamingEvent {
+    pub channel: String,
+    pub replay_id: Option<i64>,
+    pub data: Value,
+}
+
+impl StreamingEvent {
+    fn from_message(message: Message) -> StreamingEvent {
+        let data = message.data.unwrap_or(Value::Null);
+
+        StreamingEvent {
+            channel: message.channel,
+            replay_id: data.pointer("/event/replayId").and_then(|id| id.as_i64()),
+            data: data,
+        }
+    }
+
+    /// The changed record of a PushTopic event, or the payload of a platform or change event
+    pub fn body(&self) -> Option<&Value> {
+        self.data.get("sobject").or_else(|| self.data.get("payload"))
+    }
+
+    pub fn body_as<T: DeserializeOwned>(&self) -> RestResult<T> {
+        self.body()
+            .ok_or(RestError::ResponseParseFailure)
+            .and_then(|body| {
+                serde_json::from_value(body.clone()).map_err(|_| RestError::ResponseParseFailure)
+            })
+    }
+}
+
+/// The client id and cookies that tie long polling requests to one Bayeux session
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    pub client_id: String,
+    cookies: BTreeMap<String, String>,
+}
+
+impl Session {
+    fn headers(&self) -> Headers {
+        let mut headers = Headers::new();
+
+        if !self.cookies.is_empty() {
+            let cookies: Vec<String> = self.cookies
+                .iter()
+                .map(|(name, value)| name.clone() + "=" + value)
+                .collect();
+            headers.set_raw("Cookie", cookies.join("; "));
+        }
+
+        headers
+    }
+
+    fn store_cookies(&mut self, response: &Response) {
+        if let Some(raw) = response.headers().get_raw("Set-Cookie") {
+            for line in raw.iter() {
+                let line = String::from_utf8_lossy(line);
+                let pair = line.split(';').next().unwrap_or("");
+                let mut parts = pair.splitn(2, '=');
+
+                if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                    self.cookies.insert(name.trim().to_owned(), value.trim().to_owned());
+                }
+            }
+        }
+    }
+}
+
+/// Reports an unsuccessful Bayeux reply, such as `403::Unknown client`, as a failure carrying
+/// the code at the start of its error
+fn bayeux_failure(message: &Message) -> RestError {
+    let error = message.error.clone().unwrap_or_default();
+    let status = error.split("::").next().and_then(|code| code.parse().ok()).unwrap_or(400);
+
+    RestError::API(RestFailure {
+        status: status,
+        errors: vec![
+            ApiFailure {
+                message: error,
+                ..ApiFailure::default()
+            },
+        ],
+    })
+}
+
+fn cometd_url(request: &RestRequest, path: &str) -> String {
+    request.instance_url(
+        (COMETD_BASE.to_owned() + request.version().trim_left_matches('v') + path).as_str(),
+    )
+}
+
+fn exchange(
+    request: &RestRequest,
+    session: &mut Session,
+    path: &str,
+    message: &Value,
+) -> RestResult<Vec<Message>> {
+    let body = json!([message]);
+    let mut response = request.send(
+        Method::Post,
+        cometd_url(request, path).as_str(),
+        session.headers(),
+        |builder| builder.json(&body),
+    )?;
+
+    if !response.status().is_success() {
+        return Err(failure(response));
+    }
+
+    session.store_cookies(&response);
+    response.json::<Vec<Message>>().map_err(
+        |_| RestError::ResponseParseFailure,
+    )
+}
+
+fn meta_reply<'m>(messages: &'m [Message], channel: &str) -> RestResult<&'m Message> {
+    let reply = messages.iter().find(|message| message.channel == channel).ok_or(
+        RestError::ResponseParseFailure,
+    )?;
+
+    if reply.successful == Some(true) {
+        Ok(reply)
+    } else {
+        Err(bayeux_failure(reply))
+    }
+}
+
+/// Opens a new Bayeux session with replay support enabled
+pub fn handshake(request: &RestRequest) -> RestResult<Session> {
+    let mut session = Session::default();
+    let message = json!({
+        "channel": "/meta/handshake",
+        "version": "1.0",
+        "minimumVersion": "1.0",
+        "supportedConnectionTypes": ["long-polling"],
+        "ext": { "replay": true }
+    });
+
+    let messages = exchange(request, &mut session, "/handshake", &message)?;
+    session.client_id = meta_reply(&messages, "/meta/handshake")?
+        .client_id
+        .clone()
+        .ok_or(RestError::ResponseParseFailure)?;
+
+    Ok(session)
+}
+
+/// Subscribes to `channel`, replaying the events after `replay_id` or following `REPLAY_NEW` and
+/// `REPLAY_ALL`
+pub fn subscribe(
+    request: &RestRequest,
+    session: &mut Session,
+    channel: &str,
+    replay_id: i64,
+) -> RestResult<()> {
+    let mut replay = Map::new();
+    replay.insert(channel.to_owned(), Value::from(replay_id));
+
+    let message = json!({
+        "channel": "/meta/subscribe",
+        "clientId": session.client_id,
+        "subscription": channel,
+        "ext": { "replay": replay }
+    });
+
+    let messages = exchange(request, session, "", &message)?;
+    meta_reply(&messages, "/meta/subscribe").map(|_| ())
+}
+
+/// Long polls for events, returning them along with the `/meta/connect` reply. An unsuccessful
+/// reply is not an error here, its advice says whether to retry, handshake again or stop.
+pub fn connect(
+    request: &RestRequest,
+    session: &mut Session,
+) -> RestResult<(Vec<StreamingEvent>, Message)> {
+    let message = json!({
+        "channel": "/meta/connect",
+        "clientId": session.client_id,
+        "connectionType": "long-polling"
+    });
+
+    let messages = exchange(request, session, "/connect", &message)?;
+    let (replies, events): (Vec<Message>, Vec<Message>) = messages.into_iter().partition(
+        |message| message.channel.starts_with("/meta/"),
+    );
+    let reply = replies
+        .into_iter()
+        .find(|message| message.channel == "/meta/connect")
+        .ok_or(RestError::ResponseParseFailure)?;
+
+    Ok((events.into_iter().map(StreamingEvent::from_message).collect(), reply))
+}
+
+/// Whether a session survived a long poll
+enum Polled {
+    Connected,
+    /// The session is gone or could not be opened, for the given reason, and a new handshake is
+    /// needed
+    Expired(SFClientError),
+}
+
+/// Receives events from one or more channels, tracking the last replay id seen on each so that
+/// a lost session resumes where it left off. With a replay store, an event's replay id is saved
+/// once the following event is asked for, so a restarted consumer sees unfinished events again.
+pub struct Subscription<'c> {
+    client: &'c mut SFClient,
+    replay_ids: BTreeMap<String, i64>,
+    replay_store: Option<Box<ReplayStore>>,
+    delivered: Option<(String, i64)>,
+    session: Option<Session>,
+    events: VecDeque<StreamingEvent>,
+}
+
+impl<'c> Subscription<'c> {
+    pub fn new(client: &'c mut SFClient) -> Subscription<'c> {
+        Subscription {
+            client: client,
+            replay_ids: BTreeMap::new(),
+            replay_store: None,
+            delivered: None,
+            session: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Persists replay ids through `store`, channels added afterwards resume from the replay id
+    /// it holds for them
+    pub fn set_replay_store(&mut self, store: Box<ReplayStore>) {
+        self.replay_store = Some(store);
+    }
+
+    /// Adds a channel, taking effect the next time events are polled for. `replay_id` is used
+    /// when the replay store has nothing saved for the channel.
+    pub fn add_channel(&mut self, channel: &str, replay_id: i64) {
+        let replay_id = self.replay_store
+            .as_ref()
+            .and_then(|store| store.load(channel))
+            .unwrap_or(replay_id);
+        self.replay_ids.insert(channel.to_owned(), replay_id);
+
+        if let Some(mut session) = self.session.take() {
+            let subscribed = self.client.rest_once(|request| {
+                subscribe(request, &mut session, channel, replay_id)
+            });
+
+            match subscribed {
+                Ok(()) => self.session = Some(session),
+                Err(err) => warn!("Failed to subscribe to {}, starting a new session: {}", channel, err),
+            };
+        }
+    }
+
+    /// The last replay id seen on each channel
+    pub fn replay_ids(&self) -> &BTreeMap<String, i64> {
+        &self.replay_ids
+    }
+
+    /// Handshakes and subscribes to every channel, each call is made once as a failure is
+    /// followed by a new handshake from `next`
+    fn open(&mut self) -> SFClientResult<Session> {
+        let mut session = self.client.rest_once(handshake)?;
+        debug!("Started streaming session {}", session.client_id);
+
+        for (channel, replay_id) in &self.replay_ids {
+            self.client.rest_once(|request| {
+                subscribe(request, &mut session, channel, *replay_id)
+            })?;
+        }
+
+        Ok(session)
+    }
+
+    fn commit(&mut self) -> SFClientResult<()> {
+        if let (Some((channel, replay_id)), Some(store)) =
+            (self.delivered.take(), self.replay_store.as_mut())
+        {
+            store.save(channel.as_str(), replay_id).map_err(RestError::Io)?;
+        }
+
+        Ok(())
+    }
+
+    fn poll(&mut self) -> SFClientResult<Polled> {
+        let mut session = match self.session.take() {
+            Some(session) => session,
+            None => {
+                match self.open() {
+                    Ok(session) => session,
+                    Err(err) => return Ok(Polled::Expired(err)),
+                }
+            }
+        };
+
+        let connected = self.client.rest_once(|request| connect(request, &mut session));
+        let (events, reply) = match connected {
+            Ok(connected) => connected,
+            Err(err) => {
+                let rejected = match err {
+                    SFClientError::Rest(ref failure) => failure.status() == Some(401),
+                    _ => false,
+                };
+
+                if rejected {
+                    return Ok(Polled::Expired(err));
+                }
+
+                self.session = Some(session);
+                return Err(err);
+            }
+        };
+
+        for event in events {
+            if let Some(replay_id) = event.replay_id {
+                self.replay_ids.insert(event.channel.clone(), replay_id);
+            }
+            self.events.push_back(event);
+        }
+
+        let advice = reply.advice.clone().unwrap_or_default();
+        let reconnect = advice.reconnect.clone().unwrap_or_else(|| {
+            if reply.successful == Some(true) {
+                "retry".to_owned()
+            } else {
+                "handshake".to_owned()
+            }
+        });
+
+        match reconnect.as_str() {
+            "none" => return Err(SFClientError::Rest(bayeux_failure(&reply))),
+            "handshake" => return Ok(Polled::Expired(SFClientError::Rest(bayeux_failure(&reply)))),
+            _ => (),
+        };
+
+        self.session = Some(session);
+
+        if let Some(interval) = advice.interval {
+            if interval > 0 {
+                thread::sleep(Duration::from_millis(interval));
+            }
+        }
+
+        Ok(Polled::Connected)
+    }
+}
+
+impl<'c> Iterator for Subscription<'c> {
+    type Item = SFClientResult<StreamingEvent>;
+
+    /// Waits for the next event. A session the server no longer recognises, or that failed to
+    /// open, is replaced by a new handshake that resubscribes from the last replay ids, at most
+    /// twice in a row.
+    fn next(&mut self) -> Option<SFClientResult<StreamingEvent>> {
+        let mut handshakes = 0;
+
+        if let Err(err) = self.commit() {
+            return Some(Err(err));
+        }
+
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                self.delivered = event.replay_id.map(|id| (event.channel.clone(), id));
+                return Some(Ok(event));
+            }
+
+            match self.poll() {
+                Ok(Polled::Connected) => (),
+                Ok(Polled::Expired(err)) => {
+                    if handshakes < 2 {
+                        info!("Streaming session expired, handshaking again: {}", err);
+                        handshakes += 1;
+                    } else {
+                        return Some(Err(err));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::{mock, Mock};
+
+    use std::env;
+    use std::fs;
+
+    use streaming::{REPLAY_NEW, Subscription, handshake};
+    use {SFClient, SFClientError};
+    use replay::{FileReplayStore, ReplayStore};
+    use http::HttpClient;
+    use rest::RestRequest;
+    use token::TokenResponse;
+
+    fn cometd_mock(version: &str, path: &str, body: &str) -> Mock {
+        let url = "/instance/cometd/".to_owned() + version + path;
+        let mut m = mock("POST", url.as_str());
+        m.with_status(200).with_body(body);
+        m.create();
+        m
+    }
+
+    #[test]
+    fn test_handshakes_and_keeps_cookies() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/instance/";
+        let request = RestRequest::new(ep.as_str(), "v20.0", "test-token", &client);
+        let mut m = mock("POST", "/instance/cometd/20.0/handshake");
+        m.with_status(200)
+            .with_header("Set-Cookie", "BAYEUX_BROWSER=4e1a; Path=/; Secure")
+            .with_body(
+                r#"[{"channel": "/meta/handshake", "clientId": "abc1", "successful": true}]"#,
+            );
+        m.create();
+
+        let session = handshake(&request);
+
+        m.remove();
+
+        let session = session.unwrap();
+        assert_eq!("abc1", session.client_id);
+        assert_eq!(
+            Some(&"4e1a".to_owned()),
+            session.cookies.get("BAYEUX_BROWSER")
+        );
+    }
+
+    #[test]
+    fn test_delivers_events_and_tracks_replay_ids() {
+        let handshake_mock = cometd_mock(
+            "21.0",
+            "/handshake",
+            r#"[{"channel": "/meta/handshake", "clientId": "abc2", "successful": true}]"#,
+        );
+        let subscribe_mock = cometd_mock(
+            "21.0",
+            "",
+            r#"[{"channel": "/meta/subscribe", "subscription": "/topic/Accounts", "successful": true}]"#,
+        );
+        let connect_mock = cometd_mock(
+            "21.0",
+            "/connect",
+            json!([
+                {
+                    "channel": "/topic/Accounts",
+                    "data": {
+                        "event": {"replayId": 12, "type": "created"},
+                        "sobject": {"Id": "001S", "Name": "Streamed"}
+                    }
+                },
+                {"channel": "/meta/connect", "successful": true}
+            ]).to_string()
+                .as_str(),
+        );
+        let mut client = SFClient::new(
+            mockito::SERVER_URL.to_owned() + "/login",
+            "v21.0".to_owned(),
+            "id".to_owned(),
+            "secret".to_owned(),
+            "user".to_owned(),
+            "pass".to_owned(),
+        ).unwrap();
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("token", "", instance_url.as_str(), "", ""));
+
+        let (event, replay_ids) = {
+            let mut subscription = Subscription::new(&mut client);
+            subscription.add_channel("/topic/Accounts", REPLAY_NEW);
+            let event = subscription.next().unwrap();
+            (event, subscription.replay_ids().clone())
+        };
+
+        handshake_mock.remove();
+        subscribe_mock.remove();
+        connect_mock.remove();
+
+        let event = event.unwrap();
+        assert_eq!("/topic/Accounts", event.channel);
+        assert_eq!(Some(12), event.replay_id);
+        assert_eq!("Streamed", event.body().unwrap()["Name"]);
+        assert_eq!(Some(&12), replay_ids.get("/topic/Accounts"));
+    }
+
+    #[test]
+    fn test_follows_connect_advice_without_repeating_polls() {
+        let handshake_mock = cometd_mock(
+            "23.0",
+            "/handshake",
+            r#"[{"channel": "/meta/handshake", "clientId": "abc4", "successful": true}]"#,
+        );
+        let subscribe_mock = cometd_mock(
+            "23.0",
+            "",
+            r#"[{"channel": "/meta/subscribe", "subscription": "/topic/Leads", "successful": true}]"#,
+        );
+        let mut connect_mock = mock("POST", "/instance/cometd/23.0/connect");
+        connect_mock.with_status(200).with_body(
+            r#"[{"channel": "/meta/connect", "successful": false, "error": "403::Unknown client", "advice": {"reconnect": "none"}}]"#,
+        );
+        connect_mock.expect(1);
+        connect_mock.create();
+        let mut client = SFClient::new(
+            mockito::SERVER_URL.to_owned() + "/login",
+            "v23.0".to_owned(),
+            "id".to_owned(),
+            "secret".to_owned(),
+            "user".to_owned(),
+            "pass".to_owned(),
+        ).unwrap();
+        client.set_attempt_limit(3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("token", "", instance_url.as_str(), "", ""));
+
+        let event = {
+            let mut subscription = Subscription::new(&mut client);
+            subscription.add_channel("/topic/Leads", REPLAY_NEW);
+            subscription.next().unwrap()
+        };
+
+        connect_mock.assert();
+        handshake_mock.remove();
+        subscribe_mock.remove();
+        connect_mock.remove();
+
+        match event {
+            Err(SFClientError::Rest(failure)) => assert_eq!(Some(403), failure.status()),
+            _ => panic!("Failed to stop when told not to reconnect"),
+        };
+        assert_eq!("token", client.token().unwrap().access());
+    }
+
+    #[test]
+    fn test_handshakes_once_per_attempt() {
+        let mut handshake_mock = mock("POST", "/instance/cometd/24.0/handshake");
+        handshake_mock.with_status(200).with_body(
+            r#"[{"channel": "/meta/handshake", "successful": false, "error": "403::Handshake denied"}]"#,
+        );
+        handshake_mock.expect(3);
+        handshake_mock.create();
+        let mut client = SFClient::new(
+            mockito::SERVER_URL.to_owned() + "/login",
+            "v24.0".to_owned(),
+            "id".to_owned(),
+            "secret".to_owned(),
+            "user".to_owned(),
+            "pass".to_owned(),
+        ).unwrap();
+        client.set_attempt_limit(3);
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("token", "", instance_url.as_str(), "", ""));
+
+        let event = {
+            let mut subscription = Subscription::new(&mut client);
+            subscription.add_channel("/topic/Cases", REPLAY_NEW);
+            subscription.next().unwrap()
+        };
+
+        handshake_mock.assert();
+        handshake_mock.remove();
+
+        assert!(event.is_err());
+    }
+
+    #[test]
+    fn test_saves_replay_ids_of_handled_events() {
+        let path = env::temp_dir().join("micro_sf_client_streaming_replay").join("replay.json");
+        let mut store = FileReplayStore::open(path.clone()).unwrap();
+        store.save("/data/ChangeEvents", 5).unwrap();
+
+        let handshake_mock = cometd_mock(
+            "22.0",
+            "/handshake",
+            r#"[{"channel": "/meta/handshake", "clientId": "abc3", "successful": true}]"#,
+        );
+        let subscribe_mock = cometd_mock(
+            "22.0",
+            "",
+            r#"[{"channel": "/meta/subscribe", "subscription": "/data/ChangeEvents", "successful": true}]"#,
+        );
+        let connect_mock = cometd_mock(
+            "22.0",
+            "/connect",
+            json!([
+                {"channel": "/data/ChangeEvents", "data": {"event": {"replayId": 6}, "payload": {}}},
+                {"channel": "/data/ChangeEvents", "data": {"event": {"replayId": 7}, "payload": {}}},
+                {"channel": "/meta/connect", "successful": true}
+            ]).to_string()
+                .as_str(),
+        );
+        let mut client = SFClient::new(
+            mockito::SERVER_URL.to_owned() + "/login",
+            "v22.0".to_owned(),
+            "id".to_owned(),
+            "secret".to_owned(),
+            "user".to_owned(),
+            "pass".to_owned(),
+        ).unwrap();
+        let instance_url = mockito::SERVER_URL.to_owned() + "/instance/";
+        client.set_token(TokenResponse::new("token", "", instance_url.as_str(), "", ""));
+
+        let (resumed_from, first, second) = {
+            let mut subscription = Subscription::new(&mut client);
+            subscription.set_replay_store(Box::new(store));
+            subscription.add_channel("/data/ChangeEvents", REPLAY_NEW);
+            let resumed_from = subscription.replay_ids().get("/data/ChangeEvents").cloned();
+            let first = subscription.next().unwrap().unwrap();
+            let second = subscription.next().unwrap().unwrap();
+            (resumed_from, first, second)
+        };
+        let saved = FileReplayStore::open(path.clone()).unwrap().load("/data/ChangeEvents");
+
+        handshake_mock.remove();
+        subscribe_mock.remove();
+        connect_mock.remove();
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+
+        assert_eq!(Some(5), resumed_from);
+        assert_eq!(Some(6), first.replay_id);
+        assert_eq!(Some(7), second.replay_id);
+        assert_eq!(Some(6), saved);
+    }
+}
@@ -1,6 +1,7 @@
 extern crate serde_json;
 
-use reqwest::{Client, Error as ClientError, RequestBuilder};
+use reqwest::{Error as ClientError, Method};
+use reqwest::header::Headers;
 
 use std::cmp::PartialEq;
 use std::collections::HashMap;
@@ -8,14 +9,28 @@ use std::error::Error;
 use std::fmt;
 use std::io::Read;
 
-#[derive(Debug)]
+use http::HttpClient;
+use secret::Secret;
+
 pub struct TokenRequest<'a, 'b, 'c, 'd, 'e, 'f> {
     login_url: &'a str,
     client_id: &'b str,
     client_secret: &'c str,
     username: &'d str,
     password: &'e str,
-    client: &'f Client,
+    client: &'f HttpClient,
+}
+
+impl<'a, 'b, 'c, 'd, 'e, 'f> fmt::Debug for TokenRequest<'a, 'b, 'c, 'd, 'e, 'f> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TokenRequest")
+            .field("login_url", &self.login_url)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"[REDACTED]")
+            .field("username", &self.username)
+            .field("password", &"[REDACTED]")
+            .finish()
+    }
 }
 
 impl<'a, 'b, 'c, 'd, 'e, 'f> PartialEq for TokenRequest<'a, 'b, 'c, 'd, 'e, 'f> {
@@ -28,7 +43,7 @@ impl<'a, 'b, 'c, 'd, 'e, 'f> PartialEq for TokenRequest<'a, 'b, 'c, 'd, 'e, 'f>
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct TokenResponse {
-    access_token: String,
+    access_token: Secret,
     token_type: String,
     instance_url: String,
     signature: String,
@@ -87,7 +102,7 @@ impl<'a, 'b, 'c, 'd, 'e, 'f> TokenRequest<'a, 'b, 'c, 'd, 'e, 'f> {
         client_secret: &'c str,
         username: &'d str,
         password: &'e str,
-        client: &'f Client,
+        client: &'f HttpClient,
     ) -> TokenRequest<'a, 'b, 'c, 'd, 'e, 'f> {
         TokenRequest {
             login_url: login_url,
@@ -99,19 +114,23 @@ impl<'a, 'b, 'c, 'd, 'e, 'f> TokenRequest<'a, 'b, 'c, 'd, 'e, 'f> {
         }
     }
 
-    fn build_request(&self) -> RequestBuilder {
+    fn params(&self) -> HashMap<&str, &str> {
         let mut auth_params = HashMap::new();
         auth_params.insert("grant_type", "password");
         auth_params.insert("client_id", self.client_id);
         auth_params.insert("client_secret", self.client_secret);
         auth_params.insert("username", self.username);
         auth_params.insert("password", self.password);
-
-        self.client.post(self.login_url).form(&auth_params)
+        auth_params
     }
 
     pub fn send(&self) -> TokenResult {
-        let mut response = self.build_request().send().map_err(TokenError::Network)?;
+        let auth_params = self.params();
+        let mut response = self.client
+            .send(Method::Post, self.login_url, Headers::new(), |request| {
+                request.form(&auth_params)
+            })
+            .map_err(TokenError::Network)?;
 
         let mut content = String::new();
         response.read_to_string(&mut content);
@@ -140,7 +159,7 @@ impl TokenResponse {
         issued_at: &str,
     ) -> TokenResponse {
         TokenResponse {
-            access_token: access_token.to_string(),
+            access_token: Secret::new(access_token),
             token_type: token_type.to_string(),
             instance_url: instance_url.to_string(),
             signature: signature.to_string(),
@@ -153,7 +172,7 @@ impl TokenResponse {
     }
 
     pub fn access(&self) -> &str {
-        self.access_token.as_str()
+        self.access_token.expose()
     }
 }
 
@@ -199,9 +218,9 @@ impl Error for TokenError {
 mod tests {
     use mockito;
     use mockito::{mock, Mock};
-    use reqwest::Client;
     use serde_json;
 
+    use http::HttpClient;
     use token::AuthFailure;
     use token::TokenError;
     use token::TokenRequest;
@@ -264,7 +283,7 @@ mod tests {
 
     macro_rules! auth_fail_test {
         ( $error:expr, $error_value:pat, $error_msg:expr ) => {
-            let client = Client::new().unwrap();
+            let client = HttpClient::new().unwrap();
             let path = auth_path($error);
             let error = auth_err($error);
             let url = auth_url($error);
@@ -282,7 +301,7 @@ mod tests {
 
     #[test]
     fn test_auth_parses_token() {
-        let client = Client::new().unwrap();
+        let client = HttpClient::new().unwrap();
         let token = serde_json::from_str::<TokenResponse>(auth_success().as_str()).unwrap();
         let path = auth_path("auth_success");
         let url = auth_url("auth_success");
@@ -347,4 +366,27 @@ mod tests {
             "Failed to handle rate_limit_exceeded"
         );
     }
+
+    #[test]
+    fn test_debug_output_hides_secrets() {
+        let client = HttpClient::new().unwrap();
+        let url = auth_url("debug");
+        let request = TokenRequest::new(
+            url.as_str(),
+            "id",
+            "super-client-secret",
+            "user",
+            "super-password",
+            &client,
+        );
+        let token = serde_json::from_str::<TokenResponse>(auth_success().as_str()).unwrap();
+
+        let request_output = format!("{:?}", request);
+        let token_output = format!("{:?}", token);
+
+        assert!(!request_output.contains("super-client-secret"));
+        assert!(!request_output.contains("super-password"));
+        assert!(!token_output.contains(ACCESS));
+        assert_eq!(ACCESS, token.access());
+    }
 }
@@ -0,0 +1,313 @@
+use reqwest::{Method, StatusCode};
+use reqwest::header::Headers;
+use serde_json::{self, Value};
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+
+use rest::{ApiFailure, RestError, RestRequest, RestResult, encode_segment, failure, failure_from};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TreeResponse {
+    pub has_errors: bool,
+    pub results: Vec<TreeResult>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TreeResult {
+    pub reference_id: String,
+    pub id: Option<String>,
+    pub errors: Vec<ApiFailure>,
+}
+
+impl TreeResponse {
+    /// Maps each record's `attributes.referenceId` to the Id it was created with
+    pub fn references(&self) -> HashMap<String, String> {
+        self.results
+            .iter()
+            .filter_map(|result| {
+                result.id.as_ref().map(|id| (result.reference_id.clone(), id.clone()))
+            })
+            .collect()
+    }
+}
+
+/// One step of an SFDX data plan, naming the files to import for an sObject
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PlanEntry {
+    pub sobject: String,
+    pub save_refs: bool,
+    pub resolve_refs: bool,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TreeFile {
+    pub records: Vec<Value>,
+}
+
+impl TreeFile {
+    /// Every `attributes.referenceId` declared in the file, including those of nested records
+    pub fn reference_ids(&self) -> HashSet<String> {
+        let mut ids = HashSet::new();
+
+        for record in &self.records {
+            collect_reference_ids(record, &mut ids);
+        }
+
+        ids
+    }
+}
+
+fn collect_reference_ids(value: &Value, ids: &mut HashSet<String>) {
+    match *value {
+        Value::Array(ref values) => {
+            for value in values {
+                collect_reference_ids(value, ids);
+            }
+        }
+        Value::Object(ref fields) => {
+            if let Some(id) = value.pointer("/attributes/referenceId").and_then(|id| id.as_str()) {
+                ids.insert(id.to_owned());
+            }
+
+            for (name, value) in fields {
+                if name != "attributes" {
+                    collect_reference_ids(value, ids);
+                }
+            }
+        }
+        _ => (),
+    };
+}
+
+/// A problem with the records of a data plan, found before they are sent
+#[derive(Debug, PartialEq)]
+pub enum TreeError {
+    UnresolvedReference(String),
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TreeError::UnresolvedReference(ref reference) => {
+                write!(f, "Unresolved reference {}", reference)
+            }
+        }
+    }
+}
+
+impl Error for TreeError {
+    fn description(&self) -> &str {
+        match *self {
+            TreeError::UnresolvedReference(_) => "Reference was not saved by an earlier import",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        None
+    }
+}
+
+/// Replaces `@referenceId` strings anywhere in `value` with the Ids saved by earlier imports.
+/// Only strings naming one of the `declared` reference ids of the plan are treated as references,
+/// so other values that happen to start with `@` are left alone.
+pub fn resolve_references(
+    value: &mut Value,
+    declared: &HashSet<String>,
+    references: &HashMap<String, String>,
+) -> Result<(), TreeError> {
+    match *value {
+        Value::String(ref mut text) => {
+            if text.starts_with('@') && declared.contains(&text[1..]) {
+                let id = references.get(&text[1..]).cloned().ok_or_else(|| {
+                    TreeError::UnresolvedReference(text.clone())
+                })?;
+                *text = id;
+            }
+        }
+        Value::Array(ref mut values) => {
+            for value in values.iter_mut() {
+                resolve_references(value, declared, references)?;
+            }
+        }
+        Value::Object(ref mut fields) => {
+            for (name, value) in fields.iter_mut() {
+                if name != "attributes" {
+                    resolve_references(value, declared, references)?;
+                }
+            }
+        }
+        _ => (),
+    };
+
+    Ok(())
+}
+
+/// Creates a tree of records rooted at `sobject`. Records that fail validation come back as a
+/// response with `has_errors` set rather than as an error.
+pub fn create_tree(request: &RestRequest, sobject: &str, records: &[Value]) -> RestResult<TreeResponse> {
+    let path = "/composite/tree/".to_owned() + encode_segment(sobject).as_str();
+    let body = json!({ "records": records });
+
+    let mut response = request.send(
+        Method::Post,
+        request.url(path.as_str()).as_str(),
+        Headers::new(),
+        |builder| builder.json(&body),
+    )?;
+
+    match *response.status() {
+        StatusCode::Ok | StatusCode::Created => {
+            response.json::<TreeResponse>().or_else(
+                |_| Err(RestError::ResponseParseFailure),
+            )
+        }
+        StatusCode::BadRequest => {
+            let mut content = String::new();
+            response.read_to_string(&mut content).map_err(RestError::Io)?;
+
+            match serde_json::from_str::<TreeResponse>(content.as_str()) {
+                Ok(ref tree) if tree.has_errors => Ok(tree.clone()),
+                _ => Err(failure_from(400, content.as_str())),
+            }
+        }
+        _ => Err(failure(response)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito;
+    use mockito::mock;
+
+    use std::collections::HashMap;
+
+    use http::HttpClient;
+    use rest::{RestError, RestRequest};
+    use tree::{TreeError, TreeFile, create_tree, resolve_references};
+
+    #[test]
+    fn test_resolves_references() {
+        let accounts = TreeFile {
+            records: vec![
+                json!({"attributes": {"type": "Account", "referenceId": "AccountRef1"}}),
+                json!({"attributes": {"type": "Account", "referenceId": "AccountRef2"}}),
+            ],
+        };
+        let declared = accounts.reference_ids();
+        let mut references = HashMap::new();
+        references.insert("AccountRef1".to_owned(), "001A".to_owned());
+        let mut record = json!({
+            "attributes": {"type": "Contact", "referenceId": "ContactRef1"},
+            "LastName": "Test",
+            "Twitter__c": "@acme",
+            "AccountId": "@AccountRef1"
+        });
+
+        resolve_references(&mut record, &declared, &references).unwrap();
+
+        assert_eq!("001A", record["AccountId"]);
+        assert_eq!("@acme", record["Twitter__c"]);
+        assert_eq!("ContactRef1", record["attributes"]["referenceId"]);
+
+        let mut unsaved = json!({"AccountId": "@AccountRef2"});
+        assert_eq!(
+            Err(TreeError::UnresolvedReference("@AccountRef2".to_owned())),
+            resolve_references(&mut unsaved, &declared, &references)
+        );
+    }
+
+    #[test]
+    fn test_maps_references_to_ids() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!({
+            "hasErrors": false,
+            "results": [
+                {"referenceId": "ref1", "id": "001D000000K0fXOIAZ"},
+                {"referenceId": "ref2", "id": "003D000000QV9n2IAD"}
+            ]
+        });
+        let mut m = mock("POST", "/services/data/vXY.Z/composite/tree/TreeTest");
+        m.with_status(201).with_body(body.to_string().as_str());
+        m.create();
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+        let records = vec![
+            json!({
+                "attributes": {"type": "TreeTest", "referenceId": "ref1"},
+                "Name": "Test",
+                "Contacts": {"records": [
+                    {"attributes": {"type": "Contact", "referenceId": "ref2"}, "LastName": "Test"}
+                ]}
+            }),
+        ];
+
+        let tree = create_tree(&request, "TreeTest", &records).unwrap();
+
+        m.remove();
+
+        assert!(!tree.has_errors);
+        assert_eq!(
+            Some(&"003D000000QV9n2IAD".to_owned()),
+            tree.references().get("ref2")
+        );
+    }
+
+    #[test]
+    fn test_reports_record_errors() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let body = json!({
+            "hasErrors": true,
+            "results": [{
+                "referenceId": "ref2",
+                "errors": [{
+                    "statusCode": "INVALID_EMAIL_ADDRESS",
+                    "message": "Email: invalid email address: 123",
+                    "fields": ["Email"]
+                }]
+            }]
+        });
+        let mut m = mock("POST", "/services/data/vXY.Z/composite/tree/TreeErrorTest");
+        m.with_status(400).with_body(body.to_string().as_str());
+        m.create();
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let tree = create_tree(&request, "TreeErrorTest", &[json!({})]).unwrap();
+
+        m.remove();
+
+        assert!(tree.has_errors);
+        assert_eq!("INVALID_EMAIL_ADDRESS", tree.results[0].errors[0].status_code);
+    }
+
+    #[test]
+    fn test_keeps_error_codes_of_other_bad_requests() {
+        let client = HttpClient::new().unwrap();
+        let ep = mockito::SERVER_URL.to_owned() + "/";
+        let mut m = mock("POST", "/services/data/vXY.Z/composite/tree/TreeBadRequest");
+        m.with_status(400).with_body(
+            r#"[{"message": "Too many records", "errorCode": "LIMIT_EXCEEDED"}]"#,
+        );
+        m.create();
+        let request = RestRequest::new(ep.as_str(), "vXY.Z", "test-token", &client);
+
+        let res = create_tree(&request, "TreeBadRequest", &[json!({})]);
+
+        m.remove();
+
+        match res {
+            Err(RestError::API(failure)) => {
+                assert_eq!(400, failure.status);
+                assert_eq!("LIMIT_EXCEEDED", failure.errors[0].error_code);
+            }
+            _ => panic!("Failed to keep the error code of a bad request"),
+        };
+    }
+}
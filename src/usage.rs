@@ -0,0 +1,81 @@
+use std::fmt;
+
+pub static LIMIT_INFO_HEADER: &'static str = "Sforce-Limit-Info";
+
+/// The org wide API usage reported through the `Sforce-Limit-Info` header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApiUsage {
+    pub used: u64,
+    pub max: u64,
+}
+
+impl ApiUsage {
+    pub fn new(used: u64, max: u64) -> ApiUsage {
+        ApiUsage {
+            used: used,
+            max: max,
+        }
+    }
+
+    /// Parses a header value such as `api-usage=18/5000`. Other entries in the header, like
+    /// `per-app-api-usage`, are ignored.
+    pub fn parse(header: &str) -> Option<ApiUsage> {
+        header
+            .split(',')
+            .map(|entry| entry.trim())
+            .filter_map(|entry| if entry.starts_with("api-usage=") {
+                Some(&entry["api-usage=".len()..])
+            } else {
+                None
+            })
+            .filter_map(|usage| {
+                let mut parts = usage.splitn(2, '/');
+
+                match (parts.next(), parts.next()) {
+                    (Some(used), Some(max)) => {
+                        match (used.trim().parse(), max.trim().parse()) {
+                            (Ok(used), Ok(max)) => Some(ApiUsage::new(used, max)),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.max.saturating_sub(self.used)
+    }
+}
+
+impl fmt::Display for ApiUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} API requests used", self.used, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usage::ApiUsage;
+
+    #[test]
+    fn test_parses_api_usage() {
+        assert_eq!(Some(ApiUsage::new(18, 5000)), ApiUsage::parse("api-usage=18/5000"));
+    }
+
+    #[test]
+    fn test_parses_api_usage_among_other_entries() {
+        assert_eq!(
+            Some(ApiUsage::new(25, 5000)),
+            ApiUsage::parse("per-app-api-usage=17/250(appName=sample), api-usage=25/5000")
+        );
+    }
+
+    #[test]
+    fn test_ignores_malformed_usage() {
+        assert_eq!(None, ApiUsage::parse("api-usage=18"));
+        assert_eq!(None, ApiUsage::parse("api-usage=a/b"));
+        assert_eq!(None, ApiUsage::parse(""));
+    }
+}
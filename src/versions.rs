@@ -0,0 +1,125 @@
+use query::API_BASE;
+use rest::{RestRequest, RestResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiVersion {
+    pub label: String,
+    pub url: String,
+    pub version: String,
+}
+
+impl ApiVersion {
+    /// The version in the `vXX.X` form that request paths use
+    pub fn path_version(&self) -> String {
+        "v".to_owned() + self.version.as_str()
+    }
+}
+
+/// How the client settles on the API version it sends requests to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VersionPolicy {
+    /// Use the configured version without checking it against the instance
+    Pinned,
+    /// Use the configured version, failing if the instance does not support it
+    Verify,
+    /// Use the newest version the instance supports
+    Latest,
+    /// Use the newest version the instance supports that is not newer than the configured one
+    UpTo,
+}
+
+impl Default for VersionPolicy {
+    fn default() -> VersionPolicy {
+        VersionPolicy::Pinned
+    }
+}
+
+/// Splits a version such as `v41.0` or `41.0` into its major and minor parts
+pub fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let trimmed = version.trim_left_matches('v');
+    let mut parts = trimmed.splitn(2, '.');
+
+    match (parts.next(), parts.next()) {
+        (Some(major), Some(minor)) => {
+            match (major.parse(), minor.parse()) {
+                (Ok(major), Ok(minor)) => Some((major, minor)),
+                _ => None,
+            }
+        }
+        (Some(major), None) => major.parse().ok().map(|major| (major, 0)),
+        _ => None,
+    }
+}
+
+pub fn select_version<'a>(
+    versions: &'a [ApiVersion],
+    policy: VersionPolicy,
+    configured: &str,
+) -> Option<&'a ApiVersion> {
+    let wanted = parse_version(configured);
+    let mut candidates = versions.iter().filter_map(|version| {
+        parse_version(version.version.as_str()).map(|number| (number, version))
+    });
+
+    match policy {
+        VersionPolicy::Pinned | VersionPolicy::Verify => {
+            candidates
+                .find(|&(number, _)| Some(number) == wanted)
+                .map(|(_, version)| version)
+        }
+        VersionPolicy::Latest => {
+            candidates.max_by_key(|&(number, _)| number).map(
+                |(_, version)| version,
+            )
+        }
+        VersionPolicy::UpTo => {
+            candidates
+                .filter(|&(number, _)| Some(number) <= wanted)
+                .max_by_key(|&(number, _)| number)
+                .map(|(_, version)| version)
+        }
+    }
+}
+
+pub fn get_versions(request: &RestRequest) -> RestResult<Vec<ApiVersion>> {
+    request.get(request.instance_url(API_BASE).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use versions::{ApiVersion, VersionPolicy, parse_version, select_version};
+
+    fn versions() -> Vec<ApiVersion> {
+        vec!["38.0", "39.0", "40.0", "41.0"]
+            .into_iter()
+            .map(|version| {
+                ApiVersion {
+                    label: "".to_owned(),
+                    url: "/services/data/v".to_owned() + version,
+                    version: version.to_owned(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parses_versions() {
+        assert_eq!(Some((41, 0)), parse_version("v41.0"));
+        assert_eq!(Some((41, 0)), parse_version("41.0"));
+        assert_eq!(None, parse_version("latest"));
+    }
+
+    #[test]
+    fn test_selects_versions_by_policy() {
+        let available = versions();
+        let select = |policy, configured| {
+            select_version(&available, policy, configured).map(|version| version.path_version())
+        };
+
+        assert_eq!(Some("v39.0".to_owned()), select(VersionPolicy::Verify, "v39.0"));
+        assert_eq!(None, select(VersionPolicy::Verify, "v42.0"));
+        assert_eq!(Some("v41.0".to_owned()), select(VersionPolicy::Latest, "v20.0"));
+        assert_eq!(Some("v40.0".to_owned()), select(VersionPolicy::UpTo, "v40.5"));
+        assert_eq!(None, select(VersionPolicy::UpTo, "v20.0"));
+    }
+}